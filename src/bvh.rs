@@ -0,0 +1,739 @@
+//Bounding volume hierarchy, so a ray only has to be tested against the primitives near it
+//Scenes build one over their objects and meshes one over their triangles, the tree itself only ever sees boxes
+//The tree is built top down, splitting each node where the surface area heuristic predicts the cheapest traversal
+use crate::objects::Object;
+use crate::shape::HitRecord;
+use crate::shape::Shape;
+
+//Number of buckets candidate splits are evaluated at along the chosen axis
+static SAH_BINS: usize = 12;
+//Nodes with this many primitives or less are never split
+static MIN_SPLIT_SIZE: usize = 2;
+//Past this many primitives a node is split even when the heuristic says a leaf is cheaper
+static MAX_LEAF_SIZE: usize = 8;
+
+//Axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Vector3<f32>,
+    pub max: cgmath::Vector3<f32>,
+}
+
+impl Aabb {
+    //Contains nothing, growing it by anything gives back the other box / point
+    pub fn empty() -> Self {
+        Self {
+            min: cgmath::Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: cgmath::Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[cgmath::Vector3<f32>]) -> Self {
+        points
+            .iter()
+            .fold(Self::empty(), |bounds, point| bounds.grow(point))
+    }
+
+    pub fn grow(&self, point: &cgmath::Vector3<f32>) -> Self {
+        Self {
+            min: cgmath::Vector3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: cgmath::Vector3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.grow(&other.min).grow(&other.max)
+    }
+
+    pub fn centroid(&self) -> cgmath::Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let extent = self.max - self.min;
+        if extent.x < 0.0 || extent.y < 0.0 || extent.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    //Slab test, inverse_direction is 1 / direction per component so rays parallel to an axis become infinities
+    pub fn hit(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        inverse_direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        let mut t_enter = min_distance;
+        let mut t_exit = max_distance;
+        for axis in 0..3 {
+            let t_near = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t_far = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            //A parallel ray starting exactly on a slab gives 0 * infinity, it runs along the face and is not cut off by it
+            if t_near.is_nan() || t_far.is_nan() {
+                continue;
+            }
+            t_enter = t_enter.max(t_near.min(t_far));
+            t_exit = t_exit.min(t_near.max(t_far));
+            if t_exit < t_enter {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+enum BvhNode {
+    //Primitives are primitive_order[first..first + count]
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    //The left child always directly follows its parent in the node list
+    Interior {
+        bounds: Aabb,
+        right_child: usize,
+        split_axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+//A split found by the surface area heuristic, items whose centroid falls in a bin before split_bin go left
+struct Split {
+    axis: usize,
+    split_bin: usize,
+    centroid_min: f32,
+    centroid_extent: f32,
+}
+
+impl Split {
+    fn bin_of(&self, item: &BuildItem) -> usize {
+        let relative = (item.centroid[self.axis] - self.centroid_min) / self.centroid_extent;
+        ((relative * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+    }
+}
+
+struct BuildItem {
+    primitive_index: usize,
+    bounds: Aabb,
+    centroid: cgmath::Vector3<f32>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitive_order: Vec<usize>,
+    //Infinite primitives such as planes have no box, they are tested against every ray
+    unbounded_primitives: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(objects: &[Object]) -> Self {
+        let bounds: Vec<Option<Aabb>> =
+            objects.iter().map(|object| object.bounding_box()).collect();
+        Self::build_over(&bounds)
+    }
+
+    //Tree over primitives given by their boxes, the index into bounds is what gets passed back when traversing
+    pub fn build_over(bounds: &[Option<Aabb>]) -> Self {
+        let mut bvh = Bvh::default();
+        let mut items = Vec::with_capacity(bounds.len());
+        for (primitive_index, primitive_bounds) in bounds.iter().enumerate() {
+            match primitive_bounds {
+                Some(bounds) => items.push(BuildItem {
+                    primitive_index,
+                    bounds: *bounds,
+                    centroid: bounds.centroid(),
+                }),
+                None => bvh.unbounded_primitives.push(primitive_index),
+            }
+        }
+        if !items.is_empty() {
+            bvh.build_node(&mut items);
+        }
+        bvh
+    }
+
+    //Appends the node covering items (and all of its children), returning its index
+    fn build_node(&mut self, items: &mut [BuildItem]) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.union(&item.bounds));
+        let node_index = self.nodes.len();
+
+        match Self::find_split(items, &bounds) {
+            Some(split) => {
+                //Uses the same binning the split was chosen with, so neither side can end up empty
+                let left_count = partition(items, |item| split.bin_of(item) < split.split_bin);
+                self.nodes.push(BvhNode::Interior {
+                    bounds,
+                    right_child: 0,
+                    split_axis: split.axis,
+                });
+                let (left_items, right_items) = items.split_at_mut(left_count);
+                self.build_node(left_items);
+                let right_index = self.build_node(right_items);
+                if let BvhNode::Interior { right_child, .. } = &mut self.nodes[node_index] {
+                    *right_child = right_index;
+                }
+            }
+            None => {
+                let first = self.primitive_order.len();
+                self.primitive_order
+                    .extend(items.iter().map(|item| item.primitive_index));
+                self.nodes.push(BvhNode::Leaf {
+                    bounds,
+                    first,
+                    count: items.len(),
+                });
+            }
+        }
+        node_index
+    }
+
+    //Binned surface area heuristic, returns None when the items are better off as a leaf
+    fn find_split(items: &[BuildItem], bounds: &Aabb) -> Option<Split> {
+        if items.len() <= MIN_SPLIT_SIZE {
+            return None;
+        }
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.grow(&item.centroid));
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        };
+        //Every centroid is in the same spot, no split can separate them
+        if extent[axis] <= 0.0 {
+            return None;
+        }
+
+        let mut split = Split {
+            axis,
+            split_bin: 0,
+            centroid_min: centroid_bounds.min[axis],
+            centroid_extent: extent[axis],
+        };
+        let mut bin_counts = vec![0; SAH_BINS];
+        let mut bin_bounds = vec![Aabb::empty(); SAH_BINS];
+        for item in items.iter() {
+            let bin = split.bin_of(item);
+            bin_counts[bin] += 1;
+            bin_bounds[bin] = bin_bounds[bin].union(&item.bounds);
+        }
+
+        //Cost of splitting after each bin, the constant traversal cost is left out as it is the same for every split
+        let mut best_cost = f32::INFINITY;
+        for split_bin in 1..SAH_BINS {
+            let (left_count, left_bounds) =
+                sum_bins(&bin_counts[..split_bin], &bin_bounds[..split_bin]);
+            let (right_count, right_bounds) =
+                sum_bins(&bin_counts[split_bin..], &bin_bounds[split_bin..]);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left_count as f32 * left_bounds.surface_area()
+                + right_count as f32 * right_bounds.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                split.split_bin = split_bin;
+            }
+        }
+
+        let leaf_cost = items.len() as f32 * bounds.surface_area();
+        if split.split_bin == 0 || (best_cost >= leaf_cost && items.len() <= MAX_LEAF_SIZE) {
+            return None;
+        }
+        Some(split)
+    }
+
+    //Closest hit among objects between min_distance and max_distance, objects must be the list the tree was built from
+    pub fn intersect<'object_lifetime>(
+        &self,
+        objects: &'object_lifetime [Object],
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'object_lifetime>> {
+        self.closest_hit(
+            origin,
+            direction,
+            min_distance,
+            max_distance,
+            |object_index, max_distance| {
+                objects[object_index]
+                    .intersect(origin, direction, min_distance, max_distance)
+                    .map(|hit| (hit.t, hit))
+            },
+        )
+    }
+
+    //True as soon as any object blocks the ray, used for shadows where the closest hit does not matter
+    pub fn occluded(
+        &self,
+        objects: &[Object],
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        self.any_hit(
+            origin,
+            direction,
+            min_distance,
+            max_distance,
+            |object_index, max_distance| {
+                objects[object_index].occluded(origin, direction, min_distance, max_distance)
+            },
+        )
+    }
+
+    //Closest hit among the primitives, intersect_primitive is given a primitive index and the current max distance
+    //and returns the distance of its hit along with the hit itself
+    pub fn closest_hit<H, F>(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+        mut intersect_primitive: F,
+    ) -> Option<H>
+    where
+        F: FnMut(usize, f32) -> Option<(f32, H)>,
+    {
+        let mut closest = None;
+        let mut closest_t = max_distance;
+        let mut test_primitive = |primitive_index: usize, closest_t: &mut f32| {
+            //Shrinking max distance as we go means every hit found is closer than the last
+            if let Some((t, hit)) = intersect_primitive(primitive_index, *closest_t) {
+                *closest_t = t;
+                closest = Some(hit);
+            }
+            false
+        };
+        for primitive_index in self.unbounded_primitives.iter() {
+            test_primitive(*primitive_index, &mut closest_t);
+        }
+        self.traverse(
+            origin,
+            direction,
+            min_distance,
+            &mut closest_t,
+            test_primitive,
+        );
+        closest
+    }
+
+    //Whether blocks returns true for any primitive along the ray, it is given a primitive index and the max distance
+    pub fn any_hit<F>(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+        mut blocks: F,
+    ) -> bool
+    where
+        F: FnMut(usize, f32) -> bool,
+    {
+        let mut max_distance = max_distance;
+        self.unbounded_primitives
+            .iter()
+            .any(|primitive_index| blocks(*primitive_index, max_distance))
+            || self.traverse(
+                origin,
+                direction,
+                min_distance,
+                &mut max_distance,
+                |primitive_index, max_distance| blocks(primitive_index, *max_distance),
+            )
+    }
+
+    //Calls visit_primitive for every primitive in a node the ray passes through, nearest child first
+    //visit_primitive may shrink max_distance to prune the rest of the tree, returning true stops the traversal
+    fn traverse<F>(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: &mut f32,
+        mut visit_primitive: F,
+    ) -> bool
+    where
+        F: FnMut(usize, &mut f32) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inverse_direction =
+            cgmath::Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node
+                .bounds()
+                .hit(origin, &inverse_direction, min_distance, *max_distance)
+            {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for primitive_index in self.primitive_order[first..first + count].iter() {
+                        if visit_primitive(*primitive_index, max_distance) {
+                            return true;
+                        }
+                    }
+                }
+                BvhNode::Interior {
+                    right_child,
+                    split_axis,
+                    ..
+                } => {
+                    //Push the far child first so the near one is popped and tested first
+                    let left_child = node_index + 1;
+                    if direction[split_axis] < 0.0 {
+                        stack.push(left_child);
+                        stack.push(right_child);
+                    } else {
+                        stack.push(right_child);
+                        stack.push(left_child);
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+fn sum_bins(counts: &[usize], bounds: &[Aabb]) -> (usize, Aabb) {
+    counts.iter().zip(bounds.iter()).fold(
+        (0, Aabb::empty()),
+        |(total, total_bounds), (count, bounds)| (total + count, total_bounds.union(bounds)),
+    )
+}
+
+//Moves every item matching predicate to the front, returning how many matched
+fn partition<F>(items: &mut [BuildItem], predicate: F) -> usize
+where
+    F: Fn(&BuildItem) -> bool,
+{
+    let mut matched = 0;
+    for index in 0..items.len() {
+        if predicate(&items[index]) {
+            items.swap(matched, index);
+            matched += 1;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::intersect_ray_triangle;
+    use crate::objects::Mesh;
+    use crate::objects::Plane;
+    use crate::objects::Sphere;
+    use crate::properties::Color;
+    use crate::properties::Material;
+    use crate::scene::Scene;
+    use crate::textures::ColorSource;
+    use std::time::Instant;
+
+    //Small deterministic generator so the test scenes are the same on every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+    }
+
+    fn random_spheres(count: usize) -> Vec<Object> {
+        let mut random = Lcg(7);
+        let material = Material::Matte {
+            color: ColorSource::Solid(Color::<u8> {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            }),
+            normal_map: None,
+        };
+        let mut objects: Vec<Object> = (0..count)
+            .map(|_| {
+                Object::Sphere(Sphere {
+                    center: cgmath::Vector3::new(
+                        random.next() * 40.0 - 20.0,
+                        random.next() * 40.0 - 20.0,
+                        random.next() * 40.0 + 5.0,
+                    ),
+                    radius: 0.05 + random.next() * 0.3,
+                    material: material.clone(),
+                })
+            })
+            .collect();
+        objects.push(Object::Plane(Plane {
+            point: cgmath::Vector3::new(0.0, -20.0, 0.0),
+            normal: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            material,
+        }));
+        objects
+    }
+
+    fn linear_intersect<'object_lifetime>(
+        objects: &'object_lifetime [Object],
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+    ) -> Option<HitRecord<'object_lifetime>> {
+        let mut closest: Option<HitRecord> = None;
+        for object in objects.iter() {
+            let max_distance = closest.as_ref().map_or(f32::INFINITY, |hit| hit.t);
+            if let Some(hit) = object.intersect(origin, direction, 0.001, max_distance) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+
+    fn grid_directions(resolution: usize) -> Vec<cgmath::Vector3<f32>> {
+        let mut directions = Vec::new();
+        for y in 0..resolution {
+            for x in 0..resolution {
+                directions.push(cgmath::Vector3::new(
+                    x as f32 / resolution as f32 * 2.0 - 1.0,
+                    y as f32 / resolution as f32 * 2.0 - 1.0,
+                    1.0,
+                ));
+            }
+        }
+        directions
+    }
+
+    #[test]
+    fn aabb_hit_respects_distance_range() {
+        let bounds = Aabb::from_points(&[
+            cgmath::Vector3::new(-1.0, -1.0, 4.0),
+            cgmath::Vector3::new(1.0, 1.0, 6.0),
+        ]);
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let inverse_direction = cgmath::Vector3::new(f32::INFINITY, f32::INFINITY, 1.0);
+        assert!(bounds.hit(&origin, &inverse_direction, 0.0, f32::INFINITY));
+        assert!(!bounds.hit(&origin, &inverse_direction, 0.0, 3.0));
+        assert!(!bounds.hit(&origin, &inverse_direction, 7.0, f32::INFINITY));
+    }
+
+    #[test]
+    fn bvh_matches_linear_search() {
+        let objects = random_spheres(500);
+        let bvh = Bvh::build(&objects);
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        for direction in grid_directions(48) {
+            let expected = linear_intersect(&objects, &origin, &direction).map(|hit| hit.t);
+            let found = bvh
+                .intersect(&objects, &origin, &direction, 0.001, f32::INFINITY)
+                .map(|hit| hit.t);
+            assert_eq!(expected, found);
+            assert_eq!(
+                expected.is_some(),
+                bvh.occluded(&objects, &origin, &direction, 0.001, f32::INFINITY)
+            );
+        }
+    }
+
+    //Wavy height field facing the origin, two triangles per cell of a resolution by resolution grid
+    fn terrain_mesh(resolution: usize) -> Mesh {
+        let mut random = Lcg(11);
+        let mut positions = Vec::new();
+        for row in 0..=resolution {
+            for column in 0..=resolution {
+                let (x, y) = (
+                    column as f32 / resolution as f32 * 40.0 - 20.0,
+                    row as f32 / resolution as f32 * 40.0 - 20.0,
+                );
+                positions.push([
+                    x,
+                    y,
+                    20.0 + (x * 0.7).sin() * (y * 0.4).cos() + random.next(),
+                ]);
+            }
+        }
+        let stride = resolution as u32 + 1;
+        let mut indices = Vec::new();
+        for row in 0..resolution as u32 {
+            for column in 0..resolution as u32 {
+                let corner = row * stride + column;
+                indices.push([corner, corner + 1, corner + stride + 1]);
+                indices.push([corner, corner + stride + 1, corner + stride]);
+            }
+        }
+        Mesh::new(
+            positions,
+            Vec::new(),
+            Vec::new(),
+            indices,
+            Material::Matte {
+                color: ColorSource::Solid(Color::<u8> {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                    a: 255,
+                }),
+                normal_map: None,
+            },
+        )
+        .unwrap()
+    }
+
+    //Nearest hit over every triangle of the mesh, what the mesh did before it had a tree
+    fn linear_mesh_intersect(
+        mesh: &Mesh,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+    ) -> Option<f32> {
+        (0..mesh.triangle_count())
+            .filter_map(|index| {
+                intersect_ray_triangle(origin, direction, &mesh.triangle_positions(index))
+            })
+            .map(|(t, _, _)| t)
+            .filter(|t| *t >= 0.001)
+            .fold(None, |closest: Option<f32>, t| {
+                Some(closest.map_or(t, |closest| closest.min(t)))
+            })
+    }
+
+    //Run with: cargo test --release scene_bvh_speedup -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn scene_bvh_speedup() {
+        let mut scene = Scene::new();
+        scene.objects = random_spheres(5000);
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let directions = grid_directions(128);
+
+        let build_start = Instant::now();
+        scene.build_bvh();
+        let build_time = build_start.elapsed();
+
+        let linear_start = Instant::now();
+        let linear_hits = directions
+            .iter()
+            .filter(|direction| linear_intersect(&scene.objects, &origin, direction).is_some())
+            .count();
+        let linear_time = linear_start.elapsed();
+
+        let bvh_start = Instant::now();
+        let bvh_hits = directions
+            .iter()
+            .filter(|direction| {
+                scene
+                    .intersect(&origin, direction, 0.001, f32::INFINITY)
+                    .is_some()
+            })
+            .count();
+        let bvh_time = bvh_start.elapsed();
+
+        println!(
+            "{} objects, {} rays: linear {:?}, bvh {:?} (+{:?} to build), {:.1}x faster",
+            scene.objects.len(),
+            directions.len(),
+            linear_time,
+            bvh_time,
+            build_time,
+            linear_time.as_secs_f64() / bvh_time.as_secs_f64()
+        );
+        assert_eq!(linear_hits, bvh_hits);
+        assert!(bvh_time < linear_time);
+    }
+
+    //Timings are printed with: cargo test --release mesh_bvh_speedup -- --nocapture
+    #[test]
+    fn mesh_bvh_speedup() {
+        let mesh = terrain_mesh(64);
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let directions = grid_directions(32);
+
+        let linear_start = Instant::now();
+        let linear_hits: Vec<Option<f32>> = directions
+            .iter()
+            .map(|direction| linear_mesh_intersect(&mesh, &origin, direction))
+            .collect();
+        let linear_time = linear_start.elapsed();
+
+        let bvh_start = Instant::now();
+        let bvh_hits: Vec<Option<f32>> = directions
+            .iter()
+            .map(|direction| {
+                mesh.intersect(&origin, direction, 0.001, f32::INFINITY)
+                    .map(|hit| hit.t)
+            })
+            .collect();
+        let bvh_time = bvh_start.elapsed();
+        assert_eq!(linear_hits, bvh_hits);
+        for (direction, hit) in directions.iter().zip(linear_hits.iter()) {
+            assert_eq!(
+                hit.is_some(),
+                mesh.occluded(&origin, direction, 0.001, f32::INFINITY)
+            );
+        }
+
+        //Count the triangles a tree like the mesh's tests, which unlike the timings does not depend on the machine
+        let bounds: Vec<Option<Aabb>> = (0..mesh.triangle_count())
+            .map(|index| Some(Aabb::from_points(&mesh.triangle_positions(index))))
+            .collect();
+        let bvh = Bvh::build_over(&bounds);
+        let mut tested = 0;
+        for direction in directions.iter() {
+            bvh.closest_hit(
+                &origin,
+                direction,
+                0.001,
+                f32::INFINITY,
+                |index, max_distance| {
+                    tested += 1;
+                    intersect_ray_triangle(&origin, direction, &mesh.triangle_positions(index))
+                        .filter(|(t, _, _)| *t >= 0.001 && *t < max_distance)
+                        .map(|(t, _, _)| (t, ()))
+                },
+            );
+        }
+        let tested_per_ray = tested as f32 / directions.len() as f32;
+        println!(
+            "{} triangles, {} rays: linear {:?}, bvh {:?}, {:.1} triangles tested per ray",
+            mesh.triangle_count(),
+            directions.len(),
+            linear_time,
+            bvh_time,
+            tested_per_ray
+        );
+        assert!(tested_per_ray < mesh.triangle_count() as f32 / 20.0);
+    }
+}
@@ -0,0 +1,143 @@
+//The camera describes where the scene is viewed from, both renderers use it to map between the world and the screen
+use crate::serialization_defs::Vector3Def;
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+//Anything closer to the camera than this is not projected, as it would land at or behind the eye
+static NEAR_PLANE: f32 = 0.001;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct Camera {
+    #[serde(with = "Vector3Def")]
+    pub position: cgmath::Vector3<f32>,
+    #[serde(with = "Vector3Def")]
+    pub look_at: cgmath::Vector3<f32>,
+    #[serde(with = "Vector3Def")]
+    pub up: cgmath::Vector3<f32>,
+    //Vertical field of view in degrees, the horizontal one follows from the aspect ratio of the surface
+    pub fov: f32,
+}
+
+impl Default for Camera {
+    //Eye at the origin looking down +z, with a 1x1 viewport at z = 1 like the original hardcoded view
+    fn default() -> Self {
+        Self {
+            position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            look_at: cgmath::Vector3::new(0.0, 0.0, 1.0),
+            up: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            fov: 2.0 * 0.5_f32.atan().to_degrees(),
+        }
+    }
+}
+
+impl Camera {
+    //Returns the right, up and forward axes of the camera, x is right, y is up and z is into the screen
+    pub fn basis(
+        &self,
+    ) -> (
+        cgmath::Vector3<f32>,
+        cgmath::Vector3<f32>,
+        cgmath::Vector3<f32>,
+    ) {
+        let forward = (self.look_at - self.position).normalize();
+        let right = self.up.cross(forward).normalize();
+        let up = forward.cross(right);
+        (right, up, forward)
+    }
+
+    //Half the width and height of the viewport one unit in front of the camera
+    fn viewport_half_extents(&self, aspect_ratio: f32) -> cgmath::Vector2<f32> {
+        let half_height = (self.fov.to_radians() / 2.0).tan();
+        cgmath::Vector2::new(half_height * aspect_ratio, half_height)
+    }
+
+    //x and y are normalized device coordinates, -1 to 1 from the left / bottom to the right / top of the surface
+    pub fn ray_direction(&self, x: f32, y: f32, aspect_ratio: f32) -> cgmath::Vector3<f32> {
+        let (right, up, forward) = self.basis();
+        let half_extents = self.viewport_half_extents(aspect_ratio);
+        forward + right * (x * half_extents.x) + up * (y * half_extents.y)
+    }
+
+    //World to camera space, x right, y up and z the distance in front of the camera
+    pub fn view_matrix(&self) -> cgmath::Matrix4<f32> {
+        let (right, up, forward) = self.basis();
+        let eye = self.position;
+        cgmath::Matrix4::new(
+            right.x,
+            up.x,
+            forward.x,
+            0.0,
+            right.y,
+            up.y,
+            forward.y,
+            0.0,
+            right.z,
+            up.z,
+            forward.z,
+            0.0,
+            -cgmath::dot(right, eye),
+            -cgmath::dot(up, eye),
+            -cgmath::dot(forward, eye),
+            1.0,
+        )
+    }
+
+    //Camera space to clip space, after dividing by w x and y are the normalized device coordinates ray_direction takes
+    //Depth has no far plane, z / w goes from 0 at the near plane towards 1 at infinity
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> cgmath::Matrix4<f32> {
+        let half_extents = self.viewport_half_extents(aspect_ratio);
+        cgmath::Matrix4::new(
+            1.0 / half_extents.x,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0 / half_extents.y,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            1.0,
+            0.0,
+            0.0,
+            -NEAR_PLANE,
+            0.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_camera_matches_unit_viewport() {
+        let camera = Camera::default();
+        let direction = camera.ray_direction(1.0, -1.0, 1.0);
+        assert!((direction - cgmath::Vector3::new(0.5, -0.5, 1.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn matrices_invert_ray_direction() {
+        let camera = Camera {
+            position: cgmath::Vector3::new(1.0, 2.0, -3.0),
+            look_at: cgmath::Vector3::new(0.0, 0.0, 4.0),
+            up: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            fov: 70.0,
+        };
+        let aspect_ratio = 16.0 / 9.0;
+        let view_projection = camera.projection_matrix(aspect_ratio) * camera.view_matrix();
+        let point = camera.position + camera.ray_direction(0.25, -0.6, aspect_ratio) * 7.0;
+        let clip = view_projection * point.extend(1.0);
+        assert!((clip.x / clip.w - 0.25).abs() < 1e-4);
+        assert!((clip.y / clip.w + 0.6).abs() < 1e-4);
+        //w is the distance along the view direction, and depth grows with it
+        let (_, _, forward) = camera.basis();
+        assert!((clip.w - cgmath::dot(point - camera.position, forward)).abs() < 1e-4);
+        assert!(clip.z / clip.w > 0.0 && clip.z / clip.w < 1.0);
+        //Behind the camera is on the far side of the near plane
+        let behind = camera.position - camera.ray_direction(0.0, 0.0, aspect_ratio);
+        assert!((view_projection * behind.extend(1.0)).z < 0.0);
+    }
+}
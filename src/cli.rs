@@ -0,0 +1,235 @@
+//Parses the command line into the options used to drive a render
+use crate::settings::Integrator;
+use anyhow::*;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+
+pub static USAGE: &str = "Usage: software-raytracer-rs [options]
+
+Options:
+    --scene <file>      Scene description to render (default: scene.json)
+    --width <pixels>    Width of the rendered frame (default: 256)
+    --height <pixels>   Height of the rendered frame (default: 256)
+    --mode <mode>       ray-traced, rasterized, wireframe or depth (default: ray-traced)
+    --output <file>     Render once without a window and write the frame to an image (png, ppm, hdr, ...)
+    --depth <n>         Maximum number of bounces (default: 3)
+    --integrator <name> whitted or path-traced, overrides the scene's integrator
+    --samples <n>       Rays per pixel, overrides the scene's samples_per_pixel
+    --passes <n>        Passes averaged into the frame, overrides the scene's passes
+    --threads <n>       Number of threads to render with (default: one per core)
+    --help              Print this message";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderType {
+    RayTraced,
+    Rasterized,
+    //Edges of the rasterizer's triangles, drawn with lines
+    Wireframe,
+    //The rasterizer's depth buffer as greys, nearer is brighter
+    Depth,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene_path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub render_type: RenderType,
+    //When set we render headless and write the frame here instead of opening a window
+    pub output_path: Option<PathBuf>,
+    pub recursion_depth: u32,
+    //Override the scene's render settings when set
+    pub integrator: Option<Integrator>,
+    pub samples: Option<u32>,
+    pub passes: Option<u32>,
+    pub threads: usize,
+    pub show_help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scene_path: PathBuf::from("scene.json"),
+            width: 256,
+            height: 256,
+            render_type: RenderType::RayTraced,
+            output_path: None,
+            recursion_depth: 3,
+            integrator: None,
+            samples: None,
+            passes: None,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            show_help: false,
+        }
+    }
+}
+
+//Expects the arguments without the program name, i.e. env::args().skip(1)
+pub fn parse_args<I>(args: I) -> Result<Options>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => options.show_help = true,
+            "--scene" => options.scene_path = PathBuf::from(next_value(&arg, &mut args)?),
+            "--width" => {
+                let value = next_value(&arg, &mut args)?;
+                options.width = parse_dimension(&arg, &value)?;
+            }
+            "--height" => {
+                let value = next_value(&arg, &mut args)?;
+                options.height = parse_dimension(&arg, &value)?;
+            }
+            "--mode" => {
+                options.render_type = match next_value(&arg, &mut args)?.as_str() {
+                    "ray-traced" | "raytraced" => RenderType::RayTraced,
+                    "rasterized" => RenderType::Rasterized,
+                    "wireframe" => RenderType::Wireframe,
+                    "depth" => RenderType::Depth,
+                    other => bail!(
+                        "Unknown render mode '{}', \
+                         expected ray-traced, rasterized, wireframe or depth",
+                        other
+                    ),
+                }
+            }
+            "--output" => {
+                options.output_path = Some(PathBuf::from(next_value(&arg, &mut args)?));
+            }
+            "--depth" => {
+                let value = next_value(&arg, &mut args)?;
+                options.recursion_depth = value.parse().with_context(|| {
+                    format!(
+                        "{} expects a non-negative whole number, got '{}'",
+                        arg, value
+                    )
+                })?;
+            }
+            "--integrator" => {
+                options.integrator = Some(match next_value(&arg, &mut args)?.as_str() {
+                    "whitted" => Integrator::Whitted,
+                    "path-traced" | "pathtraced" => Integrator::PathTraced,
+                    other => bail!(
+                        "Unknown integrator '{}', expected whitted or path-traced",
+                        other
+                    ),
+                })
+            }
+            "--samples" => {
+                let value = next_value(&arg, &mut args)?;
+                options.samples = Some(parse_count(&arg, &value)?);
+            }
+            "--passes" => {
+                let value = next_value(&arg, &mut args)?;
+                options.passes = Some(parse_count(&arg, &value)?);
+            }
+            "--threads" => {
+                let value = next_value(&arg, &mut args)?;
+                options.threads = parse_count(&arg, &value)?;
+            }
+            other => bail!("Unknown argument '{}'", other),
+        }
+    }
+    Ok(options)
+}
+
+fn next_value<I>(flag: &str, args: &mut I) -> Result<String>
+where
+    I: Iterator<Item = String>,
+{
+    args.next()
+        .with_context(|| format!("{} expects a value", flag))
+}
+
+//Whole number greater than 0
+fn parse_count<T>(flag: &str, value: &str) -> Result<T>
+where
+    T: FromStr + PartialOrd + Default,
+{
+    value
+        .parse()
+        .ok()
+        .filter(|count| *count > T::default())
+        .with_context(|| format!("{} expects a number greater than 0, got '{}'", flag, value))
+}
+
+fn parse_dimension(flag: &str, value: &str) -> Result<u32> {
+    value
+        .parse()
+        .ok()
+        .filter(|size| *size > 0)
+        .with_context(|| {
+            format!(
+                "{} expects a size in pixels greater than 0, got '{}'",
+                flag, value
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn no_arguments_gives_defaults() {
+        let options = parse_args(Vec::new()).unwrap();
+        assert_eq!(options, Options::default());
+    }
+
+    #[test]
+    fn parses_all_options() {
+        let options = parse_args(to_args(&[
+            "--scene",
+            "other.json",
+            "--width",
+            "640",
+            "--height",
+            "480",
+            "--mode",
+            "rasterized",
+            "--output",
+            "frame.png",
+            "--depth",
+            "5",
+            "--integrator",
+            "path-traced",
+            "--samples",
+            "16",
+            "--passes",
+            "4",
+            "--threads",
+            "8",
+        ]))
+        .unwrap();
+        assert_eq!(options.scene_path, PathBuf::from("other.json"));
+        assert_eq!(options.width, 640);
+        assert_eq!(options.height, 480);
+        assert_eq!(options.render_type, RenderType::Rasterized);
+        assert_eq!(options.output_path, Some(PathBuf::from("frame.png")));
+        assert_eq!(options.recursion_depth, 5);
+        assert_eq!(options.integrator, Some(Integrator::PathTraced));
+        assert_eq!(options.samples, Some(16));
+        assert_eq!(options.passes, Some(4));
+        assert_eq!(options.threads, 8);
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        assert!(parse_args(to_args(&["--width"])).is_err());
+        assert!(parse_args(to_args(&["--width", "0"])).is_err());
+        assert!(parse_args(to_args(&["--height", "tall"])).is_err());
+        assert!(parse_args(to_args(&["--mode", "path-traced"])).is_err());
+        assert!(parse_args(to_args(&["--threads", "0"])).is_err());
+        assert!(parse_args(to_args(&["--samples", "-4"])).is_err());
+        assert!(parse_args(to_args(&["--integrator", "photon-mapped"])).is_err());
+        assert!(parse_args(to_args(&["--fast"])).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//Cuts geometry down to the part that can be drawn, so the rasterizer never sees points off the surface or behind the camera
+
+//Vertex in clip space, as produced by the camera's projection matrix, with attributes that are interpolated along when cut
+pub type ClipVertex<const N: usize> = (cgmath::Vector4<f32>, [f32; N]);
+
+//Liang–Barsky: the part of the segment inside the rectangle from min to max, None when it misses it entirely
+pub fn clip_line(
+    start: cgmath::Vector2<f32>,
+    end: cgmath::Vector2<f32>,
+    min: cgmath::Vector2<f32>,
+    max: cgmath::Vector2<f32>,
+) -> Option<(cgmath::Vector2<f32>, cgmath::Vector2<f32>)> {
+    let delta = end - start;
+    //Every boundary as p * t <= q, the segment is start + delta * t for t from 0 to 1
+    let boundaries = [
+        (-delta.x, start.x - min.x),
+        (delta.x, max.x - start.x),
+        (-delta.y, start.y - min.y),
+        (delta.y, max.y - start.y),
+    ];
+    let (mut entering, mut leaving) = (0.0f32, 1.0f32);
+    for (p, q) in boundaries.iter().copied() {
+        if p == 0.0 {
+            //Parallel to this boundary, either fully outside it or it doesn't constrain t
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            entering = entering.max(q / p);
+        } else {
+            leaving = leaving.min(q / p);
+        }
+    }
+    if entering > leaving {
+        return None;
+    }
+    Some((start + delta * entering, start + delta * leaving))
+}
+
+//Sutherland–Hodgman: the part of the convex polygon inside the view frustum, empty when none of it is
+//Clips against the near plane (z >= 0 with the camera's projection) and the four sides (-w <= x, y <= w),
+//there is no far plane to clip against
+pub fn clip_polygon<const N: usize>(polygon: &[ClipVertex<N>]) -> Vec<ClipVertex<N>> {
+    let planes: [fn(&cgmath::Vector4<f32>) -> f32; 5] = [
+        |position| position.z,
+        |position| position.w + position.x,
+        |position| position.w - position.x,
+        |position| position.w + position.y,
+        |position| position.w - position.y,
+    ];
+    let mut clipped = polygon.to_vec();
+    for distance in planes.iter() {
+        if clipped.is_empty() {
+            break;
+        }
+        let input = clipped;
+        clipped = Vec::with_capacity(input.len() + 1);
+        for (index, current) in input.iter().enumerate() {
+            let previous = &input[(index + input.len() - 1) % input.len()];
+            let (previous_distance, current_distance) =
+                (distance(&previous.0), distance(&current.0));
+            if (previous_distance >= 0.0) != (current_distance >= 0.0) {
+                //Always measured from the inside end, so triangles sharing the edge get exactly the same point
+                clipped.push(if current_distance >= 0.0 {
+                    intersect(current, current_distance, previous, previous_distance)
+                } else {
+                    intersect(previous, previous_distance, current, current_distance)
+                });
+            }
+            if current_distance >= 0.0 {
+                clipped.push(*current);
+            }
+        }
+    }
+    clipped
+}
+
+//The part of a segment in clip space in front of the near plane, the sides are left to clip_line once it is on the surface
+pub fn clip_segment(
+    start: cgmath::Vector4<f32>,
+    end: cgmath::Vector4<f32>,
+) -> Option<(cgmath::Vector4<f32>, cgmath::Vector4<f32>)> {
+    match (start.z >= 0.0, end.z >= 0.0) {
+        (true, true) => Some((start, end)),
+        (false, false) => None,
+        (true, false) => Some((start, intersect(&(start, []), start.z, &(end, []), end.z).0)),
+        (false, true) => Some((intersect(&(end, []), end.z, &(start, []), start.z).0, end)),
+    }
+}
+
+//Point where the plane crosses the edge from the inside vertex to the outside one
+fn intersect<const N: usize>(
+    inside: &ClipVertex<N>,
+    inside_distance: f32,
+    outside: &ClipVertex<N>,
+    outside_distance: f32,
+) -> ClipVertex<N> {
+    let t = inside_distance / (inside_distance - outside_distance);
+    let mut attributes = inside.1;
+    for (attribute, value) in attributes.iter_mut().enumerate() {
+        *value += (outside.1[attribute] - *value) * t;
+    }
+    (inside.0 + (outside.0 - inside.0) * t, attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn lines_are_cut_to_the_rectangle() {
+        let (min, max) = (
+            cgmath::Vector2::new(0.0, 0.0),
+            cgmath::Vector2::new(9.0, 9.0),
+        );
+        let (start, end) = clip_line(
+            cgmath::Vector2::new(-5.0, 4.0),
+            cgmath::Vector2::new(15.0, 4.0),
+            min,
+            max,
+        )
+        .unwrap();
+        assert_eq!((start.x, end.x), (0.0, 9.0));
+        //Diagonal through a corner region, entering on the left and leaving through the top
+        let (start, end) = clip_line(
+            cgmath::Vector2::new(-2.0, 5.0),
+            cgmath::Vector2::new(8.0, 15.0),
+            min,
+            max,
+        )
+        .unwrap();
+        assert!((start - cgmath::Vector2::new(0.0, 7.0)).magnitude() < 1e-5);
+        assert!((end - cgmath::Vector2::new(2.0, 9.0)).magnitude() < 1e-5);
+        //Passes by the corner without touching the rectangle
+        assert!(clip_line(
+            cgmath::Vector2::new(-5.0, 8.0),
+            cgmath::Vector2::new(2.0, 15.0),
+            min,
+            max
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn segments_are_cut_at_the_near_plane() {
+        let (front, behind) = (
+            cgmath::Vector4::new(1.0, 2.0, 3.0, 4.0),
+            cgmath::Vector4::new(-1.0, 0.0, -1.0, 0.5),
+        );
+        let (start, end) = clip_segment(behind, front).unwrap();
+        assert_eq!(end, front);
+        //Three quarters of the way from front to behind, where z reaches 0
+        assert!((start - cgmath::Vector4::new(-0.5, 0.5, 0.0, 1.375)).magnitude() < 1e-5);
+        assert!(clip_segment(behind, behind * 2.0).is_none());
+    }
+
+    #[test]
+    fn triangle_crossing_the_near_plane_becomes_a_quad() {
+        //One vertex behind the camera, its color fades from 1 there to 0 at the others
+        let triangle = [
+            (cgmath::Vector4::new(0.0, 0.0, -1.0, 0.5), [1.0]),
+            (cgmath::Vector4::new(-1.0, 0.0, 1.0, 2.0), [0.0]),
+            (cgmath::Vector4::new(1.0, 0.0, 1.0, 2.0), [0.0]),
+        ];
+        let clipped = clip_polygon(&triangle);
+        assert_eq!(clipped.len(), 4);
+        for (position, [color]) in clipped.iter() {
+            assert!(position.z >= 0.0);
+            assert!(position.x.abs() <= position.w);
+            if position.z == 0.0 {
+                //Halfway along the edges in clip space
+                assert!((color - 0.5).abs() < 1e-5);
+            }
+        }
+        //Entirely behind
+        let behind = triangle.map(|(position, color)| {
+            (
+                cgmath::Vector4::new(position.x, position.y, -1.0, position.w),
+                color,
+            )
+        });
+        assert!(clip_polygon(&behind).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//Per pixel depth for the rasterizer, so the nearest surface wins no matter what order triangles are drawn in
+//Stores the reciprocal of the distance in front of the camera (1/z), which unlike z itself varies linearly across a
+//projected triangle, and spends most of the float precision close to the camera where it is needed
+use crate::properties::Color;
+use crate::surface::Surface;
+use serde::{Deserialize, Serialize};
+
+//How a new fragment's depth is compared against the one already stored
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum DepthTest {
+    //Draw everything, like having no depth buffer
+    Always,
+    #[default]
+    Closer,
+    //Also lets a surface be drawn over itself, e.g. for decals or a second pass
+    CloserOrEqual,
+}
+
+pub struct DepthBuffer {
+    width: u32,
+    height: u32,
+    inverse_depths: Vec<f32>,
+    test: DepthTest,
+    //Whether fragments that pass the test store their depth, turned off for see through geometry
+    write: bool,
+}
+
+impl DepthBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            inverse_depths: vec![0.0; (width * height) as usize],
+            test: DepthTest::default(),
+            write: true,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    //Back to infinitely far away everywhere, call between frames
+    pub fn clear(&mut self) {
+        self.inverse_depths
+            .iter_mut()
+            .for_each(|depth| *depth = 0.0);
+    }
+
+    pub fn set_test(&mut self, test: DepthTest) {
+        self.test = test;
+    }
+
+    pub fn set_write(&mut self, write: bool) {
+        self.write = write;
+    }
+
+    //Distance in front of the camera of the nearest surface drawn at the pixel, infinity where nothing was
+    pub fn get_depth(&self, x: u32, y: u32) -> f32 {
+        1.0 / self.inverse_depths[(x + y * self.width) as usize]
+    }
+
+    //Runs the depth test for a fragment at the pixel, storing its depth if it passes and writes are on
+    pub fn test_and_write(&mut self, x: u32, y: u32, inverse_depth: f32) -> bool {
+        let stored = &mut self.inverse_depths[(x + y * self.width) as usize];
+        let passed = match self.test {
+            DepthTest::Always => true,
+            DepthTest::Closer => inverse_depth > *stored,
+            DepthTest::CloserOrEqual => inverse_depth >= *stored,
+        };
+        if passed && self.write {
+            *stored = inverse_depth;
+        }
+        passed
+    }
+
+    //Draws the buffer as greys for inspection, white for the nearest surface fading to black far away
+    pub fn visualize(&self, surface: &mut dyn Surface) {
+        let nearest = 1.0 / self.inverse_depths.iter().copied().fold(0.0, f32::max);
+        for y in 0..self.height.min(surface.get_height()) {
+            for x in 0..self.width.min(surface.get_width()) {
+                let grey = if nearest.is_finite() {
+                    (nearest / self.get_depth(x, y) * u8::MAX as f32) as u8
+                } else {
+                    0
+                };
+                surface.set_pixel(
+                    x,
+                    y,
+                    &Color::<u8> {
+                        r: grey,
+                        g: grey,
+                        b: grey,
+                        a: u8::MAX,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_tests_decide_which_fragments_pass() {
+        let mut buffer = DepthBuffer::new(2, 1);
+        assert_eq!(buffer.get_depth(0, 0), f32::INFINITY);
+        //Closer, the default, wants a strictly larger 1/z
+        assert!(buffer.test_and_write(0, 0, 0.5));
+        assert_eq!(buffer.get_depth(0, 0), 2.0);
+        assert!(!buffer.test_and_write(0, 0, 0.5));
+        assert!(!buffer.test_and_write(0, 0, 0.25));
+        assert!(buffer.test_and_write(0, 0, 1.0));
+        assert_eq!(buffer.get_depth(0, 0), 1.0);
+
+        buffer.set_test(DepthTest::CloserOrEqual);
+        assert!(buffer.test_and_write(0, 0, 1.0));
+        assert!(!buffer.test_and_write(0, 0, 0.5));
+
+        buffer.set_test(DepthTest::Always);
+        assert!(buffer.test_and_write(0, 0, 0.25));
+        assert_eq!(buffer.get_depth(0, 0), 4.0);
+        //Other pixels are untouched
+        assert_eq!(buffer.get_depth(1, 0), f32::INFINITY);
+    }
+
+    #[test]
+    fn writes_can_be_turned_off_and_cleared() {
+        let mut buffer = DepthBuffer::new(2, 2);
+        buffer.test_and_write(1, 1, 0.5);
+        buffer.set_write(false);
+        //Still tested against what is stored, but leaves it as is
+        assert!(buffer.test_and_write(1, 1, 1.0));
+        assert!(!buffer.test_and_write(1, 1, 0.25));
+        assert_eq!(buffer.get_depth(1, 1), 2.0);
+        assert!(buffer.test_and_write(0, 1, 0.1));
+        assert_eq!(buffer.get_depth(0, 1), f32::INFINITY);
+
+        buffer.set_write(true);
+        buffer.clear();
+        assert_eq!(buffer.get_depth(1, 1), f32::INFINITY);
+        assert!(buffer.test_and_write(1, 1, 0.1));
+    }
+}
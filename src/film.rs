@@ -0,0 +1,117 @@
+//High dynamic range frame the ray tracer renders into, colors are linear and unbounded
+//Every render pass is added on top of the previous ones, so noisy renders converge progressively
+//They are only brought down to 8 bits when the film is developed onto a surface for display or export
+use crate::properties::Color;
+use crate::properties::BLACK;
+use crate::surface::Surface;
+use crate::tone_mapping::ToneMapping;
+use anyhow::*;
+use image::codecs::hdr::HdrEncoder;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+pub struct Film {
+    width: u32,
+    height: u32,
+    //Sum of every finished pass, divided by the number of passes when read
+    sums: Vec<Color<f32>>,
+    passes: u32,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            sums: vec![BLACK; (width * height) as usize],
+            passes: 0,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    //Number of passes accumulated so far
+    pub fn get_passes(&self) -> u32 {
+        self.passes
+    }
+
+    //Average of all passes, black before the first one has finished
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color<f32> {
+        if self.passes == 0 {
+            return BLACK;
+        }
+        self.sums[(x + y * self.width) as usize] * (1.0 / self.passes as f32)
+    }
+
+    //Adds the pixel's color for the pass in progress, call finish_pass once every pixel has been added
+    pub fn add_sample(&mut self, x: u32, y: u32, color: Color<f32>) {
+        let index = (x + y * self.width) as usize;
+        self.sums[index] = self.sums[index] + color;
+    }
+
+    pub fn finish_pass(&mut self) {
+        self.passes += 1;
+    }
+
+    //Tone maps every pixel and encodes it as 8 bit sRGB, the surface must be the same size as the film
+    pub fn develop(&self, surface: &mut dyn Surface, tone_mapping: &ToneMapping, exposure: f32) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mapped = tone_mapping.apply(self.get_pixel(x, y), exposure);
+                surface.set_pixel(x, y, &mapped.to_srgb8());
+            }
+        }
+    }
+
+    //Writes the unclamped linear colors as a Radiance .hdr image
+    pub fn save_hdr(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create image {}", path.display()))?;
+        //Rows are stored bottom up like the surfaces, image files are top down
+        let mut rows = Vec::with_capacity((self.width * self.height) as usize);
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.get_pixel(x, y);
+                rows.push(image::Rgb([
+                    color.r.max(0.0),
+                    color.g.max(0.0),
+                    color.b.max(0.0),
+                ]));
+            }
+        }
+        HdrEncoder::new(BufWriter::new(file))
+            .encode(&rows, self.width as usize, self.height as usize)
+            .with_context(|| format!("Could not write image {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_are_averaged() {
+        let mut film = Film::new(2, 1);
+        let grey = |value: f32| Color::<f32> {
+            r: value,
+            g: value,
+            b: value,
+            a: 1.0,
+        };
+        for value in [1.0, 2.0, 6.0].iter() {
+            film.add_sample(1, 0, grey(*value));
+            film.finish_pass();
+        }
+        assert_eq!(film.get_passes(), 3);
+        assert_eq!(film.get_pixel(0, 0).r, 0.0);
+        assert!((film.get_pixel(1, 0).g - 3.0).abs() < 1e-6);
+    }
+}
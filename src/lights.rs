@@ -1,7 +1,11 @@
 //Defines the different forms of lights that a scene can possess
 use crate::properties::Color;
+use crate::sampling::concentric_disk;
 use crate::serialization_defs::Vector3Def;
+use crate::shape::orthonormal_basis;
+use cgmath::InnerSpace;
 use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
 //Intensities should probably be only 3 channel, as how can you somehow have more / less transparent light??
 #[derive(Serialize, Deserialize, Debug)]
@@ -18,5 +22,266 @@ pub enum Light {
         #[serde(with = "Vector3Def")]
         position: cgmath::Vector3<f32>,
         intensity: Color<f32>,
+        #[serde(default)]
+        attenuation: Attenuation,
     },
+    //Point light that only shines inside a cone around direction
+    Spot {
+        #[serde(with = "Vector3Def")]
+        position: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        direction: cgmath::Vector3<f32>,
+        //Half angles of the cone in degrees, full intensity inside inner_angle fading to nothing at outer_angle
+        inner_angle: f32,
+        outer_angle: f32,
+        intensity: Color<f32>,
+        #[serde(default)]
+        attenuation: Attenuation,
+    },
+    //Light given off by a whole surface, which casts soft shadows
+    //The ray tracer shades it like the intensity split between samples point lights spread over the shape,
+    //the path tracer treats intensity as the radiance leaving the surface and takes one sample per bounce
+    Area {
+        shape: AreaShape,
+        intensity: Color<f32>,
+        //Shadow rays per shaded point, rounded to the nearest square for stratification
+        #[serde(default = "default_area_light_samples")]
+        samples: u32,
+        #[serde(default)]
+        attenuation: Attenuation,
+    },
+}
+
+fn default_area_light_samples() -> u32 {
+    16
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub enum AreaShape {
+    //Parallelogram spanned by the two edges, centered on center
+    Rectangle {
+        #[serde(with = "Vector3Def")]
+        center: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        edge_u: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        edge_v: cgmath::Vector3<f32>,
+    },
+    Disk {
+        #[serde(with = "Vector3Def")]
+        center: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        normal: cgmath::Vector3<f32>,
+        radius: f32,
+    },
+    Sphere {
+        #[serde(with = "Vector3Def")]
+        center: cgmath::Vector3<f32>,
+        radius: f32,
+    },
+    Triangle {
+        #[serde(with = "Vector3Def")]
+        a: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        b: cgmath::Vector3<f32>,
+        #[serde(with = "Vector3Def")]
+        c: cgmath::Vector3<f32>,
+    },
+}
+
+//Surface of an emissive object, sampled like an area light giving off radiance
+#[derive(Debug, Copy, Clone)]
+pub struct Emitter {
+    pub shape: AreaShape,
+    pub radiance: Color<f32>,
+}
+
+impl AreaShape {
+    //Maps a point of the unit square onto the shape, keeping stratified samples evenly spread
+    //Spheres are only sampled on the half facing viewer, the other half can never be seen from there
+    pub fn sample(&self, u: f32, v: f32, viewer: &cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+        match *self {
+            AreaShape::Rectangle {
+                center,
+                edge_u,
+                edge_v,
+            } => center + edge_u * (u - 0.5) + edge_v * (v - 0.5),
+            AreaShape::Disk {
+                center,
+                normal,
+                radius,
+            } => {
+                let (tangent, bitangent) = orthonormal_basis(&normal.normalize());
+                let (x, y) = concentric_disk(u, v);
+                center + (tangent * x + bitangent * y) * radius
+            }
+            AreaShape::Sphere { center, radius } => {
+                let axis = (viewer - center).normalize();
+                let (tangent, bitangent) = orthonormal_basis(&axis);
+                //Uniform over the hemisphere, height along the axis is uniformly distributed
+                let height = u;
+                let ring_radius = (1.0 - height * height).max(0.0).sqrt();
+                let angle = 2.0 * PI * v;
+                center
+                    + (tangent * (ring_radius * angle.cos())
+                        + bitangent * (ring_radius * angle.sin())
+                        + axis * height)
+                        * radius
+            }
+            AreaShape::Triangle { a, b, c } => {
+                //Square root warps the samples so they are uniform over the area instead of bunching at a
+                let root = u.sqrt();
+                a * (1.0 - root) + b * (root * (1.0 - v)) + c * (root * v)
+            }
+        }
+    }
+
+    pub fn center(&self) -> cgmath::Vector3<f32> {
+        match *self {
+            AreaShape::Rectangle { center, .. }
+            | AreaShape::Disk { center, .. }
+            | AreaShape::Sphere { center, .. } => center,
+            AreaShape::Triangle { a, b, c } => (a + b + c) / 3.0,
+        }
+    }
+
+    //Area the samples are spread over, the probability density of a sample per unit area is one over this
+    pub fn sampled_area(&self) -> f32 {
+        match *self {
+            AreaShape::Rectangle { edge_u, edge_v, .. } => edge_u.cross(edge_v).magnitude(),
+            AreaShape::Disk { radius, .. } => PI * radius * radius,
+            AreaShape::Sphere { radius, .. } => 2.0 * PI * radius * radius,
+            AreaShape::Triangle { a, b, c } => (b - a).cross(c - a).magnitude() / 2.0,
+        }
+    }
+
+    //Unit normal at a point on the shape, flat shapes give off light from both sides so theirs faces the viewer
+    pub fn normal_at(
+        &self,
+        point: &cgmath::Vector3<f32>,
+        viewer: &cgmath::Vector3<f32>,
+    ) -> cgmath::Vector3<f32> {
+        let normal = match *self {
+            AreaShape::Rectangle { edge_u, edge_v, .. } => edge_u.cross(edge_v).normalize(),
+            AreaShape::Disk { normal, .. } => normal.normalize(),
+            AreaShape::Sphere { center, .. } => return (point - center).normalize(),
+            AreaShape::Triangle { a, b, c } => (b - a).cross(c - a).normalize(),
+        };
+        if cgmath::dot(normal, viewer - point) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+}
+
+//How the intensity of a positioned light drops with the distance d from it
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum Falloff {
+    //Same intensity at every distance, how lights behaved before falloff was added
+    #[default]
+    None,
+    //1 / d^2, what a real point source does
+    InverseSquare,
+    //1 / (constant + linear * d + quadratic * d^2)
+    Polynomial {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(default)]
+pub struct Attenuation {
+    pub falloff: Falloff,
+    //Distance past which the light has no effect, the falloff is windowed so it reaches zero smoothly
+    pub range: Option<f32>,
+}
+
+impl Attenuation {
+    //Factor the intensity is scaled by at the given distance from the light
+    pub fn factor(&self, distance: f32) -> f32 {
+        let falloff = match self.falloff {
+            Falloff::None => 1.0,
+            Falloff::InverseSquare => 1.0 / (distance * distance).max(f32::EPSILON),
+            Falloff::Polynomial {
+                constant,
+                linear,
+                quadratic,
+            } => {
+                1.0 / (constant + linear * distance + quadratic * distance * distance)
+                    .max(f32::EPSILON)
+            }
+        };
+        let window = match self.range {
+            Some(range) => {
+                let ratio = distance / range;
+                (1.0 - ratio.powi(4)).max(0.0).powi(2)
+            }
+            None => 1.0,
+        };
+        falloff * window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_falls_off_and_cuts_off_at_range() {
+        assert_eq!(Attenuation::default().factor(100.0), 1.0);
+        let inverse_square = Attenuation {
+            falloff: Falloff::InverseSquare,
+            range: None,
+        };
+        assert!((inverse_square.factor(2.0) - 0.25).abs() < 1e-6);
+        let polynomial = Attenuation {
+            falloff: Falloff::Polynomial {
+                constant: 1.0,
+                linear: 0.5,
+                quadratic: 0.25,
+            },
+            range: Some(4.0),
+        };
+        assert!(polynomial.factor(1.0) < 1.0 / 1.75);
+        assert!(polynomial.factor(3.9) > 0.0);
+        assert_eq!(polynomial.factor(4.0), 0.0);
+        assert_eq!(polynomial.factor(5.0), 0.0);
+    }
+
+    #[test]
+    fn area_samples_land_on_the_shape() {
+        let center = cgmath::Vector3::new(1.0, 2.0, 3.0);
+        let viewer = cgmath::Vector3::new(1.0, 10.0, 3.0);
+        let disk = AreaShape::Disk {
+            center,
+            normal: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            radius: 0.5,
+        };
+        let sphere = AreaShape::Sphere {
+            center,
+            radius: 0.5,
+        };
+        let triangle = AreaShape::Triangle {
+            a: center,
+            b: center + cgmath::Vector3::new(1.0, 0.0, 0.0),
+            c: center + cgmath::Vector3::new(0.0, 0.0, 1.0),
+        };
+        for step in 0..=10 {
+            let u = step as f32 / 10.0;
+            let v = 1.0 - u * 0.7;
+            let on_disk = disk.sample(u, v, &viewer) - center;
+            assert!(on_disk.y.abs() < 1e-6 && on_disk.magnitude() <= 0.5 + 1e-5);
+            let on_sphere = sphere.sample(u, v, &viewer) - center;
+            assert!((on_sphere.magnitude() - 0.5).abs() < 1e-5);
+            //On the half facing the viewer
+            assert!(on_sphere.y >= -1e-6);
+            let on_triangle = triangle.sample(u, v, &viewer) - center;
+            assert!(on_triangle.y.abs() < 1e-6);
+            assert!(on_triangle.x >= -1e-6 && on_triangle.z >= -1e-6);
+            assert!(on_triangle.x + on_triangle.z <= 1.0 + 1e-5);
+        }
+    }
 }
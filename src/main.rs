@@ -1,56 +1,85 @@
 mod buffer_primitives;
+mod bvh;
+mod camera;
+mod cli;
+mod clipping;
+mod depth_buffer;
+mod film;
 mod lights;
+mod noise;
 mod objects;
+mod path_tracer;
+mod pbr;
 mod properties;
 mod rasterizer;
 mod ray_tracer;
+mod sampling;
 mod scene;
 mod serialization_defs;
+mod settings;
+mod shape;
 mod state;
+mod surface;
+mod tessellation;
 mod texture;
+mod textures;
+mod tone_mapping;
 
 use winit::{
+    dpi::PhysicalSize,
     event::{ElementState, Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
+use anyhow::{bail, Context, Result};
+use cli::Options;
+use cli::RenderType;
+use depth_buffer::DepthBuffer;
+use film::Film;
 use futures::executor::block_on;
-use properties::Color;
 use rasterizer::clear_screen;
-use rasterizer::draw_line;
+use rasterizer::draw_scene;
+use rasterizer::draw_scene_wireframe;
 use ray_tracer::ray_trace;
 use scene::Scene;
 use state::State;
-use state::Surface;
+use surface::PixelSurface;
+use surface::Surface;
 
+use std::env;
 use std::fs;
+use std::path::Path;
+use std::process;
 
-enum RenderType {
-    RayTraced,
-    Rasterized,
-}
-
-fn run() {
+fn run(options: Options, scene: Scene) {
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new()
+        .with_inner_size(PhysicalSize::new(options.width, options.height))
+        .build(&event_loop)
+        .unwrap();
     window.set_title("software-graphics");
 
     //Initialize actual activity here
-    let mut state = Box::new(block_on(State::new(&window)));
+    let mut state = Box::new(block_on(State::new(&window, options.width, options.height)));
+    //Ray traced passes build up in the film over the frames until the scene's pass count is reached
+    let mut film = Film::new(options.width, options.height);
+    //Kept between frames like the film and cleared before every rasterized one
+    let mut depth_buffer = DepthBuffer::new(options.width, options.height);
     //finish game state intialize
 
-    let scene_filename = "scene.json";
-    let scene_json = fs::read_to_string(scene_filename)
-        .unwrap_or_else(|_| panic!("Could not read file {}", scene_filename));
-    let scene = Scene::load(&scene_json).unwrap();
-
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
         match event {
             Event::RedrawRequested(window_id) if window_id == window.id() => {
                 //Render updates state to be drawn, should probably live in state
-                render(RenderType::Rasterized, &mut state.pixel_surface, &scene);
+                render(
+                    &options,
+                    &mut state.pixel_surface,
+                    &mut film,
+                    &mut depth_buffer,
+                    &scene,
+                );
                 state.update();
                 match state.render() {
                     Ok(_) => {}
@@ -105,26 +134,126 @@ fn run() {
     });
 }
 
-fn render(render_mode: RenderType, surface: &mut dyn Surface, scene: &Scene) {
-    match render_mode {
-        RenderType::RayTraced => ray_trace(&scene, surface),
-        RenderType::Rasterized => {
-            let p1 = cgmath::Vector2::<f32> { x: 10.0, y: 10.0 };
-            let p2 = cgmath::Vector2::<f32> { x: 200.0, y: 100.0 };
-            let color = Color::<u8> {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: 255,
-            };
-            clear_screen(surface);
-            draw_line(p1, p2, color, surface);
-            let p3 = cgmath::Vector2::<f32> { x: 15.0, y: 200.0 };
-            draw_line(p1, p3, color, surface);
+//Renders a single frame without creating a window or touching the gpu
+fn run_headless(options: &Options, scene: &Scene, output_path: &Path) -> Result<()> {
+    //.hdr keeps the linear film as is instead of developing it down to 8 bits
+    let is_hdr = output_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+    let mut surface = PixelSurface::new(options.width, options.height);
+    match options.render_type {
+        RenderType::RayTraced => {
+            let mut film = Film::new(options.width, options.height);
+            while film.get_passes() < scene.settings.passes {
+                ray_trace(scene, &mut film, options.recursion_depth, options.threads);
+            }
+            if is_hdr {
+                return film.save_hdr(output_path);
+            }
+            film.develop(
+                &mut surface,
+                &scene.settings.tone_mapping,
+                scene.settings.exposure,
+            );
+        }
+        RenderType::Rasterized | RenderType::Wireframe | RenderType::Depth => {
+            if is_hdr {
+                bail!("Only ray traced frames can be written as .hdr");
+            }
+            let mut depth_buffer = DepthBuffer::new(options.width, options.height);
+            rasterize(options.render_type, &mut surface, &mut depth_buffer, scene);
+        }
+    }
+    surface.save(output_path)
+}
+
+fn load_scene(scene_path: &Path) -> Result<Scene> {
+    let scene_json = fs::read_to_string(scene_path)
+        .with_context(|| format!("Could not read scene file {}", scene_path.display()))?;
+    let mut scene = Scene::load(&scene_json)
+        .with_context(|| format!("Could not parse scene file {}", scene_path.display()))?;
+    scene.load_images(scene_path.parent().unwrap_or_else(|| Path::new("")))?;
+    Ok(scene)
+}
+
+//Ray traced frames add one more pass to the film each call until the scene's pass count is reached
+fn render(
+    options: &Options,
+    surface: &mut dyn Surface,
+    film: &mut Film,
+    depth_buffer: &mut DepthBuffer,
+    scene: &Scene,
+) {
+    match options.render_type {
+        RenderType::RayTraced => {
+            if film.get_passes() < scene.settings.passes {
+                ray_trace(scene, film, options.recursion_depth, options.threads);
+                film.develop(
+                    surface,
+                    &scene.settings.tone_mapping,
+                    scene.settings.exposure,
+                );
+            }
+        }
+        RenderType::Rasterized | RenderType::Wireframe | RenderType::Depth => {
+            rasterize(options.render_type, surface, depth_buffer, scene)
         }
     }
 }
 
+fn rasterize(
+    render_type: RenderType,
+    surface: &mut dyn Surface,
+    depth_buffer: &mut DepthBuffer,
+    scene: &Scene,
+) {
+    clear_screen(surface);
+    if render_type == RenderType::Wireframe {
+        draw_scene_wireframe(scene, surface);
+        return;
+    }
+    depth_buffer.clear();
+    draw_scene(scene, surface, depth_buffer);
+    if render_type == RenderType::Depth {
+        depth_buffer.visualize(surface);
+    }
+}
+
 fn main() {
-    run();
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Error: {}\n\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.show_help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let mut scene = load_scene(&options.scene_path).unwrap_or_else(|error| {
+        eprintln!("Error: {:#}", error);
+        process::exit(1);
+    });
+    if let Some(integrator) = options.integrator {
+        scene.settings.integrator = integrator;
+    }
+    if let Some(samples) = options.samples {
+        scene.settings.samples_per_pixel = samples;
+    }
+    if let Some(passes) = options.passes {
+        scene.settings.passes = passes;
+    }
+
+    //--output renders straight to an image instead of opening a window
+    match options.output_path.clone() {
+        Some(output_path) => {
+            if let Err(error) = run_headless(&options, &scene, &output_path) {
+                eprintln!("Error: {:#}", error);
+                process::exit(1);
+            }
+        }
+        None => run(options, scene),
+    }
 }
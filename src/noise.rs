@@ -0,0 +1,97 @@
+//Ken Perlin's improved gradient noise, the building block of the procedural textures
+//Lattice gradients come from hashing the cell coordinates, so there is no permutation table to set up
+
+//Edge midpoints of a cube, the gradient set of improved Perlin noise
+static GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+//Smooth noise roughly in -1 to 1, zero at every integer lattice point
+pub fn perlin(point: &cgmath::Vector3<f32>) -> f32 {
+    let cell = cgmath::Vector3::new(point.x.floor(), point.y.floor(), point.z.floor());
+    let offset = point - cell;
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let influence = |dx: i32, dy: i32, dz: i32| {
+        let gradient = GRADIENTS[(hash(x + dx, y + dy, z + dz) % 12) as usize];
+        cgmath::dot(
+            cgmath::Vector3::from(gradient),
+            offset - cgmath::Vector3::new(dx as f32, dy as f32, dz as f32),
+        )
+    };
+    let (u, v, w) = (fade(offset.x), fade(offset.y), fade(offset.z));
+    let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, influence(0, 0, 0), influence(1, 0, 0)),
+            lerp(u, influence(0, 1, 0), influence(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, influence(0, 0, 1), influence(1, 0, 1)),
+            lerp(u, influence(0, 1, 1), influence(1, 1, 1)),
+        ),
+    )
+}
+
+//Sum of octaves of absolute noise, each twice the frequency and half the amplitude of the one before
+//Gives the billowy look of smoke and the veins of marble, roughly in 0 to 1
+pub fn turbulence(point: &cgmath::Vector3<f32>, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    for _ in 0..octaves {
+        total += amplitude * perlin(&(point * frequency)).abs();
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    total
+}
+
+//Quintic curve 6t^5 - 15t^4 + 10t^3, its first and second derivatives vanish at the cell borders
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+//Integer hash of a lattice point, only needs to look random
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846c_a68b);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perlin_is_zero_on_the_lattice_and_bounded_between() {
+        assert_eq!(perlin(&cgmath::Vector3::new(3.0, -2.0, 5.0)), 0.0);
+        let mut largest: f32 = 0.0;
+        for step in 0..1000 {
+            let t = step as f32 * 0.037;
+            let value = perlin(&cgmath::Vector3::new(t, t * 0.7 + 0.3, -t * 1.3));
+            assert!(value.abs() <= 1.1);
+            largest = largest.max(value.abs());
+        }
+        //Not flat either
+        assert!(largest > 0.2);
+    }
+}
@@ -1,9 +1,94 @@
-use crate::properties::Color;
+use crate::bvh::Aabb;
+use crate::bvh::Bvh;
 use crate::properties::Material;
 use crate::serialization_defs::Vector3Def;
-use serde::{Deserialize, Serialize};
+use crate::shape::orthonormal_basis;
+use crate::shape::HitRecord;
+use crate::shape::Shape;
+use cgmath::InnerSpace;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::f32::consts::PI;
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+//Everything that can be placed in a scene and hit by a ray, new primitives only need a variant here and a Shape impl
+//Scenes name the variant, e.g. {"Sphere": {...}}, the serde impls below also accept the older bare sphere form
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(remote = "Self")]
+pub enum Object {
+    Sphere(Sphere),
+    Plane(Plane),
+    Triangle(Triangle),
+    Mesh(Mesh),
+}
+
+impl Object {
+    pub fn get_material(&self) -> &Material {
+        match self {
+            Object::Sphere(sphere) => &sphere.material,
+            Object::Plane(plane) => &plane.material,
+            Object::Triangle(triangle) => &triangle.material,
+            Object::Mesh(mesh) => &mesh.material,
+        }
+    }
+
+    fn shape(&self) -> &dyn Shape {
+        match self {
+            Object::Sphere(sphere) => sphere,
+            Object::Plane(plane) => plane,
+            Object::Triangle(triangle) => triangle,
+            Object::Mesh(mesh) => mesh,
+        }
+    }
+}
+
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Object::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        //Before there were other primitives scenes listed spheres directly, without naming the variant
+        if value.get("center").is_some() {
+            return Sphere::deserialize(value)
+                .map(Object::Sphere)
+                .map_err(D::Error::custom);
+        }
+        Object::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+impl Shape for Object {
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        self.shape()
+            .intersect(origin, direction, min_distance, max_distance)
+    }
+
+    fn occluded(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        self.shape()
+            .occluded(origin, direction, min_distance, max_distance)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.shape().bounding_box()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sphere {
     #[serde(with = "Vector3Def")]
     pub center: cgmath::Vector3<f32>,
@@ -12,10 +97,616 @@ pub struct Sphere {
 }
 
 impl Sphere {
-    pub fn get_color(self) -> Color<u8> {
-        match self.material {
-            Material::Matte { color } => color,
-            Material::Specular { color, .. } => color,
+    //returns the determinants of the quadratic equation, f32::INFINITY(no intersection), both determinants equal (tangent), two solutions (intersection)
+    fn determinants(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        ray_direction: &cgmath::Vector3<f32>,
+    ) -> (f32, f32) {
+        let radius = self.radius;
+        let origin_sphere = origin - self.center;
+        //Quadratic equation
+        let a = cgmath::dot(*ray_direction, *ray_direction);
+        let b = 2.0 * cgmath::dot(origin_sphere, *ray_direction);
+        let c = cgmath::dot(origin_sphere, origin_sphere) - radius * radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            (f32::INFINITY, f32::INFINITY)
+        } else {
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+            let t2 = (-b - discriminant.sqrt()) / (2.0 * a);
+            (t1, t2)
         }
     }
 }
+
+impl Shape for Sphere {
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        let (t1, t2) = self.determinants(origin, direction);
+        let distance_range = min_distance..max_distance;
+        //Take the nearest determinant in range, which is the far one when starting inside the sphere
+        let t = [t1.min(t2), t1.max(t2)]
+            .iter()
+            .copied()
+            .find(|t| distance_range.contains(t))?;
+        let point = origin + direction * t;
+        let outward_normal = (point - self.center) / self.radius;
+        //Longitude around y and latitude from the bottom pole
+        let uv = cgmath::Vector2::new(
+            0.5 + outward_normal.z.atan2(outward_normal.x) / (2.0 * PI),
+            0.5 + outward_normal.y.clamp(-1.0, 1.0).asin() / PI,
+        );
+        //Along the circle of latitude, and towards the top pole along the meridian
+        let u_direction = cgmath::Vector3::new(-outward_normal.z, 0.0, outward_normal.x);
+        let v_direction = cgmath::Vector3::unit_y() - outward_normal * outward_normal.y;
+        Some(HitRecord::new(
+            t,
+            point,
+            outward_normal,
+            direction,
+            uv,
+            (u_direction, v_direction),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = cgmath::Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb {
+            min: self.center - radius,
+            max: self.center + radius,
+        })
+    }
+}
+
+//Infinite plane passing through point, the normal does not need to be normalized
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Plane {
+    #[serde(with = "Vector3Def")]
+    pub point: cgmath::Vector3<f32>,
+    #[serde(with = "Vector3Def")]
+    pub normal: cgmath::Vector3<f32>,
+    pub material: Material,
+}
+
+impl Shape for Plane {
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        let denominator = cgmath::dot(self.normal, *direction);
+        //Parallel rays never meet the plane
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = cgmath::dot(self.point - origin, self.normal) / denominator;
+        if !(min_distance..max_distance).contains(&t) {
+            return None;
+        }
+        let point = origin + direction * t;
+        //Planar coordinates measured in world units from the plane's anchor point
+        let (tangent, bitangent) = orthonormal_basis(&self.normal.normalize());
+        let offset = point - self.point;
+        let uv = cgmath::Vector2::new(cgmath::dot(offset, tangent), cgmath::dot(offset, bitangent));
+        Some(HitRecord::new(
+            t,
+            point,
+            self.normal,
+            direction,
+            uv,
+            (tangent, bitangent),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+//Single flat shaded triangle, vertices are wound counter clockwise when looking at the front face
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Triangle {
+    #[serde(with = "Vector3Def")]
+    pub a: cgmath::Vector3<f32>,
+    #[serde(with = "Vector3Def")]
+    pub b: cgmath::Vector3<f32>,
+    #[serde(with = "Vector3Def")]
+    pub c: cgmath::Vector3<f32>,
+    pub material: Material,
+}
+
+impl Shape for Triangle {
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        let (t, u, v) = intersect_ray_triangle(origin, direction, &[self.a, self.b, self.c])?;
+        if !(min_distance..max_distance).contains(&t) {
+            return None;
+        }
+        Some(HitRecord::new(
+            t,
+            origin + direction * t,
+            (self.b - self.a).cross(self.c - self.a),
+            direction,
+            cgmath::Vector2::new(u, v),
+            (self.b - self.a, self.c - self.a),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[self.a, self.b, self.c]))
+    }
+}
+
+//Indexed triangle mesh, every entry of indices is one triangle made of three positions
+//Normals are optional, when given there must be one per position and they are interpolated for smooth shading
+//Loading a mesh checks it and builds its tree the same way Mesh::new does
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(remote = "Self")]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    #[serde(default)]
+    pub normals: Vec<[f32; 3]>,
+    //Texture coordinates per position, without them the barycentric coordinates of each triangle are used
+    #[serde(default)]
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: Material,
+    //Tree over the triangles, built whenever a mesh is made
+    #[serde(skip)]
+    bvh: Bvh,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Result<Self, String> {
+        let mut mesh = Mesh {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+            bvh: Bvh::default(),
+        };
+        //Building the tree looks up positions by index, so they have to be checked first
+        mesh.validate()?;
+        mesh.build_bvh();
+        Ok(mesh)
+    }
+
+    //Call again after changing the positions or indices
+    pub fn build_bvh(&mut self) {
+        let bounds: Vec<Option<Aabb>> = (0..self.triangle_count())
+            .map(|index| Some(Aabb::from_points(&self.triangle_positions(index))))
+            .collect();
+        self.bvh = Bvh::build_over(&bounds);
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn triangle_positions(&self, triangle_index: usize) -> [cgmath::Vector3<f32>; 3] {
+        let indices = self.indices[triangle_index];
+        [
+            self.positions[indices[0] as usize].into(),
+            self.positions[indices[1] as usize].into(),
+            self.positions[indices[2] as usize].into(),
+        ]
+    }
+
+    //Falls back to the face normal when the mesh has no vertex normals
+    pub fn interpolated_normal(
+        &self,
+        triangle_index: usize,
+        u: f32,
+        v: f32,
+    ) -> cgmath::Vector3<f32> {
+        if self.normals.is_empty() {
+            let [a, b, c] = self.triangle_positions(triangle_index);
+            return (b - a).cross(c - a);
+        }
+        let indices = self.indices[triangle_index];
+        let normal = |vertex: usize| -> cgmath::Vector3<f32> {
+            self.normals[indices[vertex] as usize].into()
+        };
+        normal(0) * (1.0 - u - v) + normal(1) * u + normal(2) * v
+    }
+
+    //Falls back to the barycentric coordinates when the mesh has no texture coordinates
+    pub fn interpolated_uv(&self, triangle_index: usize, u: f32, v: f32) -> cgmath::Vector2<f32> {
+        if self.uvs.is_empty() {
+            return cgmath::Vector2::new(u, v);
+        }
+        let indices = self.indices[triangle_index];
+        let uv =
+            |vertex: usize| -> cgmath::Vector2<f32> { self.uvs[indices[vertex] as usize].into() };
+        uv(0) * (1.0 - u - v) + uv(1) * u + uv(2) * v
+    }
+
+    //How positions on the triangle change with its texture coordinates, solved from the uv differences along two edges
+    //Without texture coordinates, or when they are degenerate, the barycentric coordinates are used like in interpolated_uv
+    pub fn position_derivatives(
+        &self,
+        triangle_index: usize,
+    ) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let [a, b, c] = self.triangle_positions(triangle_index);
+        let (edge_1, edge_2) = (b - a, c - a);
+        if self.uvs.is_empty() {
+            return (edge_1, edge_2);
+        }
+        let indices = self.indices[triangle_index];
+        let uv =
+            |vertex: usize| -> cgmath::Vector2<f32> { self.uvs[indices[vertex] as usize].into() };
+        let (delta_1, delta_2) = (uv(1) - uv(0), uv(2) - uv(0));
+        let determinant = delta_1.x * delta_2.y - delta_2.x * delta_1.y;
+        if determinant.abs() < f32::EPSILON {
+            return (edge_1, edge_2);
+        }
+        (
+            (edge_1 * delta_2.y - edge_2 * delta_1.y) / determinant,
+            (edge_2 * delta_1.x - edge_1 * delta_2.x) / determinant,
+        )
+    }
+
+    //Checks the indices and normals line up with the positions, so rendering never indexes out of bounds
+    fn validate(&self) -> Result<(), String> {
+        if !self.normals.is_empty() && self.normals.len() != self.positions.len() {
+            return Err(format!(
+                "Mesh has {} normals but {} positions",
+                self.normals.len(),
+                self.positions.len()
+            ));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != self.positions.len() {
+            return Err(format!(
+                "Mesh has {} texture coordinates but {} positions",
+                self.uvs.len(),
+                self.positions.len()
+            ));
+        }
+        for (triangle_index, indices) in self.indices.iter().enumerate() {
+            if indices
+                .iter()
+                .any(|index| *index as usize >= self.positions.len())
+            {
+                return Err(format!(
+                    "Mesh triangle {} references a position that does not exist",
+                    triangle_index
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    //Ray parameter and barycentric coordinates of the hit on a single triangle, if it lies in the distance range
+    fn intersect_triangle(
+        &self,
+        triangle_index: usize,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<(f32, f32, f32)> {
+        let (t, u, v) =
+            intersect_ray_triangle(origin, direction, &self.triangle_positions(triangle_index))?;
+        if t >= min_distance && t < max_distance {
+            Some((t, u, v))
+        } else {
+            None
+        }
+    }
+}
+
+impl Serialize for Mesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Mesh::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mesh {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mesh = Mesh::deserialize(deserializer)?;
+        Mesh::new(
+            mesh.positions,
+            mesh.normals,
+            mesh.uvs,
+            mesh.indices,
+            mesh.material,
+        )
+        .map_err(D::Error::custom)
+    }
+}
+
+impl Shape for Mesh {
+    //Only the triangles whose boxes the ray passes through are tested
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        let (t, index, u, v) = self.bvh.closest_hit(
+            origin,
+            direction,
+            min_distance,
+            max_distance,
+            |index, max_distance| {
+                self.intersect_triangle(index, origin, direction, min_distance, max_distance)
+                    .map(|(t, u, v)| (t, (t, index, u, v)))
+            },
+        )?;
+        //The face decides which side was hit, the interpolated normal is only used for shading
+        let [a, b, c] = self.triangle_positions(index);
+        let mut hit = HitRecord::new(
+            t,
+            origin + direction * t,
+            (b - a).cross(c - a),
+            direction,
+            self.interpolated_uv(index, u, v),
+            self.position_derivatives(index),
+            &self.material,
+        );
+        let shading_normal = self.interpolated_normal(index, u, v).normalize();
+        hit.set_shading_normal(if cgmath::dot(shading_normal, hit.normal) < 0.0 {
+            -shading_normal
+        } else {
+            shading_normal
+        });
+        Some(hit)
+    }
+
+    fn occluded(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        self.bvh.any_hit(
+            origin,
+            direction,
+            min_distance,
+            max_distance,
+            |index, max_distance| {
+                self.intersect_triangle(index, origin, direction, min_distance, max_distance)
+                    .is_some()
+            },
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let positions: Vec<cgmath::Vector3<f32>> = self
+            .positions
+            .iter()
+            .map(|position| (*position).into())
+            .collect();
+        Some(Aabb::from_points(&positions))
+    }
+}
+
+//Moller-Trumbore, returns the ray parameter and the barycentric coordinates u and v of the hit
+//u weights the second vertex and v the third, so the hit point is (1 - u - v) * a + u * b + v * c
+pub fn intersect_ray_triangle(
+    origin: &cgmath::Vector3<f32>,
+    ray_direction: &cgmath::Vector3<f32>,
+    vertices: &[cgmath::Vector3<f32>; 3],
+) -> Option<(f32, f32, f32)> {
+    let edge_1 = vertices[1] - vertices[0];
+    let edge_2 = vertices[2] - vertices[0];
+    let p = ray_direction.cross(edge_2);
+    let determinant = cgmath::dot(edge_1, p);
+    //Scale the parallel test by the edge lengths so tiny and huge triangles are treated alike
+    if determinant.abs() < f32::EPSILON * edge_1.magnitude() * edge_2.magnitude() {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - vertices[0];
+    let u = cgmath::dot(to_origin, p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge_1);
+    let v = cgmath::dot(*ray_direction, q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = cgmath::dot(edge_2, q) * inverse_determinant;
+    Some((t, u, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::properties::Color;
+    use crate::textures::ColorSource;
+
+    fn matte() -> Material {
+        Material::Matte {
+            color: ColorSource::Solid(Color::<u8> {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            }),
+            normal_map: None,
+        }
+    }
+
+    #[test]
+    fn objects_load_tagged_and_as_bare_spheres() {
+        let material = r#""material": {"Matte": {"color": [255, 0, 0, 255]}}"#;
+        let tagged = format!(
+            r#"{{"Plane": {{"point": {{"x": 0.0, "y": -1.0, "z": 0.0}}, "normal": {{"x": 0.0, "y": 1.0, "z": 0.0}}, {}}}}}"#,
+            material
+        );
+        assert!(matches!(
+            serde_json::from_str::<Object>(&tagged).unwrap(),
+            Object::Plane(_)
+        ));
+        let bare = format!(
+            r#"{{"center": {{"x": 0.0, "y": 0.0, "z": 3.0}}, "radius": 1.0, {}}}"#,
+            material
+        );
+        match serde_json::from_str::<Object>(&bare).unwrap() {
+            Object::Sphere(sphere) => assert_eq!(sphere.radius, 1.0),
+            other => panic!("Expected a sphere, got {:?}", other),
+        }
+        //Written back out in the tagged form
+        let written = serde_json::to_value(serde_json::from_str::<Object>(&bare).unwrap()).unwrap();
+        assert!(written.get("Sphere").is_some());
+        assert!(serde_json::from_str::<Object>(r#"{"Cube": {}}"#).is_err());
+    }
+
+    #[test]
+    fn ray_hits_plane_in_front() {
+        let plane = Plane {
+            point: cgmath::Vector3::new(0.0, -1.0, 0.0),
+            normal: cgmath::Vector3::new(0.0, 2.0, 0.0),
+            material: matte(),
+        };
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let hit = plane
+            .intersect(
+                &origin,
+                &cgmath::Vector3::new(0.0, -1.0, 1.0),
+                0.0,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-6);
+        assert_eq!(hit.normal, cgmath::Vector3::new(0.0, 1.0, 0.0));
+        let parallel = plane.intersect(
+            &origin,
+            &cgmath::Vector3::new(0.0, 0.0, 1.0),
+            0.0,
+            f32::INFINITY,
+        );
+        assert!(parallel.is_none());
+    }
+
+    #[test]
+    fn plane_normal_faces_the_ray() {
+        let plane = Plane {
+            point: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            normal: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            material: matte(),
+        };
+        let below = cgmath::Vector3::new(0.0, -1.0, 0.0);
+        let hit = plane
+            .intersect(
+                &below,
+                &cgmath::Vector3::new(0.0, 1.0, 0.0),
+                0.0,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert_eq!(hit.normal, cgmath::Vector3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn ray_inside_sphere_hits_far_side() {
+        let sphere = Sphere {
+            center: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            radius: 2.0,
+            material: matte(),
+        };
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let hit = sphere
+            .intersect(
+                &origin,
+                &cgmath::Vector3::new(0.0, 0.0, 1.0),
+                0.001,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-6);
+        assert_eq!(hit.normal, cgmath::Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn ray_hits_triangle_with_barycentrics() {
+        let vertices = [
+            cgmath::Vector3::new(0.0, 0.0, 2.0),
+            cgmath::Vector3::new(1.0, 0.0, 2.0),
+            cgmath::Vector3::new(0.0, 1.0, 2.0),
+        ];
+        let origin = cgmath::Vector3::new(0.25, 0.5, 0.0);
+        let (t, u, v) =
+            intersect_ray_triangle(&origin, &cgmath::Vector3::new(0.0, 0.0, 1.0), &vertices)
+                .unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+        assert!((u - 0.25).abs() < 1e-6);
+        assert!((v - 0.5).abs() < 1e-6);
+
+        let outside = cgmath::Vector3::new(0.75, 0.75, 0.0);
+        assert!(
+            intersect_ray_triangle(&outside, &cgmath::Vector3::new(0.0, 0.0, 1.0), &vertices)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn mesh_normals_are_interpolated() {
+        let mesh = Mesh::new(
+            vec![[0.0, 0.0, 2.0], [1.0, 0.0, 2.0], [0.0, 1.0, 2.0]],
+            vec![[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+            Vec::new(),
+            vec![[0, 1, 2]],
+            matte(),
+        )
+        .unwrap();
+        let origin = cgmath::Vector3::new(0.5, 0.0, 0.0);
+        let direction = cgmath::Vector3::new(0.0, 0.0, 1.0);
+        let hit = mesh
+            .intersect(&origin, &direction, 0.0, f32::INFINITY)
+            .unwrap();
+        let expected = cgmath::Vector3::new(0.5, 0.0, -0.5).normalize();
+        assert!((hit.normal - expected).magnitude() < 1e-5);
+        assert_eq!(hit.uv, cgmath::Vector2::new(0.5, 0.0));
+    }
+
+    #[test]
+    fn mesh_tangents_follow_texture_coordinates() {
+        //Texture mirrored left to right and stretched, u runs along -x and v along +y
+        let mesh = Mesh::new(
+            vec![[0.0, 0.0, 2.0], [2.0, 0.0, 2.0], [0.0, 3.0, 2.0]],
+            Vec::new(),
+            vec![[1.0, 0.0], [0.0, 0.0], [1.0, 1.0]],
+            vec![[0, 1, 2]],
+            matte(),
+        )
+        .unwrap();
+        let hit = mesh
+            .intersect(
+                &cgmath::Vector3::new(0.5, 0.5, 0.0),
+                &cgmath::Vector3::new(0.0, 0.0, 1.0),
+                0.0,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.tangent - cgmath::Vector3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        assert!((hit.bitangent - cgmath::Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+    }
+}
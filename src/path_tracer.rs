@@ -0,0 +1,323 @@
+//Monte Carlo path tracer, follows one random path per camera ray and adds up the light reaching every point along it
+//Averaged over many samples and passes this converges to the full global illumination solution
+use crate::lights::Light;
+use crate::pbr::PbrBrdf;
+use crate::properties::Color;
+use crate::properties::Material;
+use crate::properties::BG_COLOR;
+use crate::properties::BLACK;
+use crate::ray_tracer::point_in_shadow;
+use crate::ray_tracer::reflect_ray;
+use crate::ray_tracer::refract_ray;
+use crate::ray_tracer::sample_area_light;
+use crate::ray_tracer::sample_emitters;
+use crate::ray_tracer::schlick_reflectance;
+use crate::ray_tracer::spot_cone_factor;
+use crate::sampling::cosine_hemisphere;
+use crate::sampling::Rng;
+use crate::scene::Scene;
+use crate::shape::orthonormal_basis;
+use crate::shape::HitRecord;
+use cgmath::InnerSpace;
+use std::f32::consts::PI;
+
+//Bounces before paths start being randomly terminated, survivors are weighted up so the result stays unbiased
+static RUSSIAN_ROULETTE_DEPTH: u32 = 3;
+
+//What happens to a path where it hits a surface
+enum Scatter {
+    //Lambertian reflection, lit directly by the lights and continued in a cosine weighted direction
+    Diffuse {
+        albedo: Color<f32>,
+    },
+    //Metallic-roughness surface, lit directly like a diffuse one and continued along a sampled direction
+    Glossy {
+        brdf: PbrBrdf,
+    },
+    //A single possible direction such as a mirror reflection or a refraction
+    Specular {
+        direction: cgmath::Vector3<f32>,
+        weight: Color<f32>,
+    },
+    //The path ends here, emissive surfaces only give off light
+    Absorbed,
+}
+
+//Radiance arriving at the origin along the ray, the background acts as a uniform sky light
+//Ambient lights are ignored since the bounced light they approximate is now computed
+pub fn trace_path(
+    origin: &cgmath::Vector3<f32>,
+    direction: &cgmath::Vector3<f32>,
+    scene: &Scene,
+    max_bounces: u32,
+    rng: &mut Rng,
+) -> Color<f32> {
+    let mut radiance = BLACK;
+    //Fraction of the light found further along the path that makes it back to the camera
+    let mut throughput = Color::<f32> {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    let mut ray_origin = *origin;
+    let mut ray_direction = *direction;
+    //Camera rays start at the viewport like in the ray tracer
+    let mut min_distance = 1.0;
+    let mut counts_emission = true;
+    for bounce in 0..=max_bounces {
+        let mut hit =
+            match scene.intersect(&ray_origin, &ray_direction, min_distance, f32::INFINITY) {
+                Some(hit) => hit,
+                None => {
+                    radiance = radiance + throughput * BG_COLOR.to_linear();
+                    break;
+                }
+            };
+        let shading_normal = hit.material.shading_normal(&hit);
+        hit.set_shading_normal(shading_normal);
+        //Light sampling already counted emitters seen from diffuse surfaces, only camera and specular rays add them here
+        if counts_emission {
+            radiance = radiance + throughput * hit.material.at(&hit.uv, &hit.point).get_emission();
+        }
+        let incoming = ray_direction.normalize();
+        let next_direction = match scatter(&hit, &incoming, rng) {
+            Scatter::Diffuse { albedo } => {
+                //Next event estimation, light sources are sampled directly at every diffuse vertex
+                radiance = radiance + throughput * direct_lighting(&hit, scene, rng, &|_| albedo);
+                throughput = throughput * albedo;
+                counts_emission = false;
+                let (tangent, bitangent) = orthonormal_basis(&hit.normal);
+                let (x, y, z) = cosine_hemisphere(rng.next_f32(), rng.next_f32());
+                tangent * x + bitangent * y + hit.normal * z
+            }
+            Scatter::Glossy { brdf } => {
+                let view = -incoming;
+                let reflectance =
+                    |light: &cgmath::Vector3<f32>| brdf.evaluate(&hit.normal, &view, light) * PI;
+                radiance = radiance + throughput * direct_lighting(&hit, scene, rng, &reflectance);
+                counts_emission = false;
+                match brdf.sample(&hit.normal, &view, rng) {
+                    Some((direction, weight)) => {
+                        throughput = throughput * weight;
+                        direction
+                    }
+                    None => break,
+                }
+            }
+            Scatter::Specular { direction, weight } => {
+                throughput = throughput * weight;
+                counts_emission = true;
+                direction
+            }
+            Scatter::Absorbed => break,
+        };
+        if bounce >= RUSSIAN_ROULETTE_DEPTH {
+            let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+            if rng.next_f32() >= survival {
+                break;
+            }
+            throughput = throughput * (1.0 / survival);
+        }
+        ray_origin = hit.point;
+        ray_direction = next_direction;
+        min_distance = 0.0001;
+    }
+    radiance
+}
+
+//Picks how the path continues, materials that mix behaviours choose one at random in proportion to its weight
+//The Phong highlight of specular materials is left out, their mirror reflection is kept
+fn scatter(hit: &HitRecord, incoming: &cgmath::Vector3<f32>, rng: &mut Rng) -> Scatter {
+    match hit.material.at(&hit.uv, &hit.point) {
+        Material::Emissive { .. } => Scatter::Absorbed,
+        Material::Pbr {
+            base_color,
+            metallic,
+            roughness,
+            ..
+        } => Scatter::Glossy {
+            brdf: PbrBrdf::new(base_color.get_color(), metallic, roughness),
+        },
+        Material::Matte { color, .. } => Scatter::Diffuse {
+            albedo: color.get_color(),
+        },
+        Material::Specular {
+            color,
+            reflectiveness,
+            ..
+        } => {
+            if rng.next_f32() < reflectiveness {
+                Scatter::Specular {
+                    direction: reflect_ray(&hit.normal, &-*incoming),
+                    weight: Color::<f32> {
+                        r: 1.0,
+                        g: 1.0,
+                        b: 1.0,
+                        a: 1.0,
+                    },
+                }
+            } else {
+                Scatter::Diffuse {
+                    albedo: color.get_color(),
+                }
+            }
+        }
+        Material::Dielectric {
+            color,
+            refractive_index,
+            ..
+        } => {
+            let eta = if hit.front_face {
+                1.0 / refractive_index
+            } else {
+                refractive_index
+            };
+            let reflected = Scatter::Specular {
+                direction: reflect_ray(&hit.normal, &-*incoming),
+                weight: Color::<f32> {
+                    r: 1.0,
+                    g: 1.0,
+                    b: 1.0,
+                    a: 1.0,
+                },
+            };
+            match refract_ray(incoming, &hit.normal, eta) {
+                Some(refracted) => {
+                    let cos_outer = if eta > 1.0 {
+                        cgmath::dot(refracted, -hit.normal)
+                    } else {
+                        cgmath::dot(-*incoming, hit.normal)
+                    };
+                    if rng.next_f32() < schlick_reflectance(cos_outer, refractive_index) {
+                        reflected
+                    } else {
+                        Scatter::Specular {
+                            direction: refracted,
+                            weight: color.get_color(),
+                        }
+                    }
+                }
+                None => reflected,
+            }
+        }
+    }
+}
+
+//Light reflected towards the viewer directly from the scene's lights and emissive objects
+//reflectance gives pi times the BRDF for a unit direction towards the light, which is just the albedo for diffuse surfaces
+//Point, spot and directional lights give the same direct lighting as the ray tracer
+fn direct_lighting(
+    hit: &HitRecord,
+    scene: &Scene,
+    rng: &mut Rng,
+    reflectance: &dyn Fn(&cgmath::Vector3<f32>) -> Color<f32>,
+) -> Color<f32> {
+    let mut total = BLACK;
+    let mut add_light = |to_light: &cgmath::Vector3<f32>, intensity: &Color<f32>| {
+        let light = to_light.normalize();
+        let cosine = cgmath::dot(hit.normal, light);
+        if cosine > 0.0 {
+            total = total + reflectance(&light) * *intensity * cosine;
+        }
+    };
+    for light in scene.lights.iter() {
+        match light {
+            Light::Directional {
+                direction,
+                intensity,
+            } => {
+                if !point_in_shadow(&hit.point, direction, f32::INFINITY, scene) {
+                    add_light(direction, intensity);
+                }
+            }
+            Light::Point {
+                position,
+                intensity,
+                attenuation,
+            } => {
+                let to_light = position - hit.point;
+                if !point_in_shadow(&hit.point, &to_light, 1.0, scene) {
+                    add_light(
+                        &to_light,
+                        &(*intensity * attenuation.factor(to_light.magnitude())),
+                    );
+                }
+            }
+            Light::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+                intensity,
+                attenuation,
+            } => {
+                let to_light = position - hit.point;
+                let falloff = spot_cone_factor(&-to_light, direction, *inner_angle, *outer_angle)
+                    * attenuation.factor(to_light.magnitude());
+                if falloff > 0.0 && !point_in_shadow(&hit.point, &to_light, 1.0, scene) {
+                    add_light(&to_light, &(*intensity * falloff));
+                }
+            }
+            Light::Area {
+                shape,
+                intensity,
+                attenuation,
+                ..
+            } => {
+                if let Some((to_light, arriving)) = sample_area_light(
+                    shape,
+                    intensity,
+                    attenuation,
+                    (rng.next_f32(), rng.next_f32()),
+                    &hit.point,
+                    scene,
+                ) {
+                    add_light(&to_light, &arriving);
+                }
+            }
+            Light::Ambient { .. } => {}
+        }
+    }
+    let pick = rng.next_f32();
+    if let Some((to_light, arriving)) =
+        sample_emitters(scene, pick, (rng.next_f32(), rng.next_f32()), &hit.point)
+    {
+        add_light(&to_light, &arriving);
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_floor_under_the_sky_reflects_the_sky() {
+        //Every bounce off a white diffuse floor escapes to the background, so each path returns it exactly
+        let scene = Scene::load(
+            r#"{
+                "objects": [{"Plane": {
+                    "point": {"x": 0.0, "y": -1.0, "z": 0.0},
+                    "normal": {"x": 0.0, "y": 1.0, "z": 0.0},
+                    "material": {"Matte": {"color": [255, 255, 255, 255]}}
+                }}],
+                "lights": []
+            }"#,
+        )
+        .unwrap();
+        let background = BG_COLOR.to_linear();
+        let mut rng = Rng::new(7);
+        for _ in 0..16 {
+            let color = trace_path(
+                &cgmath::Vector3::new(0.0, 0.0, 0.0),
+                &cgmath::Vector3::new(0.0, -1.0, 2.0),
+                &scene,
+                4,
+                &mut rng,
+            );
+            assert!((color.r - background.r).abs() < 1e-5);
+            assert!((color.g - background.g).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//Metallic-roughness BRDF as used by glTF and most authoring tools, a GGX microfacet specular lobe over a Lambertian diffuse one
+use crate::properties::Color;
+use crate::sampling::cosine_hemisphere;
+use crate::sampling::Rng;
+use crate::shape::orthonormal_basis;
+use cgmath::InnerSpace;
+use std::f32::consts::PI;
+
+//Reflectance at normal incidence of non-metals, which all sit close to 4%
+static DIELECTRIC_REFLECTANCE: f32 = 0.04;
+//Perfectly smooth GGX is a delta function, keep a sliver of roughness so it stays finite
+static MIN_ALPHA: f32 = 0.002;
+
+//Material parameters in linear space, ready to be evaluated
+#[derive(Debug, Copy, Clone)]
+pub struct PbrBrdf {
+    base_color: Color<f32>,
+    metallic: f32,
+    //GGX alpha, the square of the perceptual roughness
+    alpha: f32,
+}
+
+impl PbrBrdf {
+    //base_color is linear
+    pub fn new(base_color: Color<f32>, metallic: f32, roughness: f32) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Self {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+        }
+    }
+
+    pub fn get_roughness(&self) -> f32 {
+        self.alpha.sqrt()
+    }
+
+    //Albedo of the diffuse lobe, metals have none
+    pub fn diffuse_color(&self) -> Color<f32> {
+        self.base_color * (1.0 - self.metallic)
+    }
+
+    //Fresnel reflectance at normal incidence, tinted by the base color for metals
+    pub fn specular_color(&self) -> Color<f32> {
+        let dielectric = Color::<f32> {
+            r: DIELECTRIC_REFLECTANCE,
+            g: DIELECTRIC_REFLECTANCE,
+            b: DIELECTRIC_REFLECTANCE,
+            a: 1.0,
+        };
+        dielectric * (1.0 - self.metallic) + self.base_color * self.metallic
+    }
+
+    //Schlick's approximation for the fraction of light reflected at the given angle
+    pub fn fresnel(&self, cosine: f32) -> Color<f32> {
+        let f0 = self.specular_color();
+        let weight = (1.0 - cosine.clamp(0.0, 1.0)).powi(5);
+        Color::<f32> {
+            r: f0.r + (1.0 - f0.r) * weight,
+            g: f0.g + (1.0 - f0.g) * weight,
+            b: f0.b + (1.0 - f0.b) * weight,
+            a: 1.0,
+        }
+    }
+
+    //BRDF value for unit vectors pointing away from the surface towards the viewer and the light
+    pub fn evaluate(
+        &self,
+        normal: &cgmath::Vector3<f32>,
+        view: &cgmath::Vector3<f32>,
+        light: &cgmath::Vector3<f32>,
+    ) -> Color<f32> {
+        let normal_dot_view = cgmath::dot(*normal, *view);
+        let normal_dot_light = cgmath::dot(*normal, *light);
+        if normal_dot_view <= 0.0 || normal_dot_light <= 0.0 {
+            return Color::<f32> {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            };
+        }
+        let half = (view + light).normalize();
+        let fresnel = self.fresnel(cgmath::dot(*view, half));
+        let specular = fresnel
+            * (self.distribution(cgmath::dot(*normal, half))
+                * self.visibility(normal_dot_view, normal_dot_light));
+        //Light reflected by the specular lobe is not available to the diffuse one
+        let not_reflected = Color::<f32> {
+            r: 1.0 - fresnel.r,
+            g: 1.0 - fresnel.g,
+            b: 1.0 - fresnel.b,
+            a: 1.0,
+        };
+        specular + not_reflected * self.diffuse_color() * (1.0 / PI)
+    }
+
+    //Picks the direction a path continues in, returning it with the BRDF times the cosine over the probability density
+    //Half the samples follow the GGX lobe for non-metals and all of them for pure metals
+    pub fn sample(
+        &self,
+        normal: &cgmath::Vector3<f32>,
+        view: &cgmath::Vector3<f32>,
+        rng: &mut Rng,
+    ) -> Option<(cgmath::Vector3<f32>, Color<f32>)> {
+        let specular_probability = 0.5 + 0.5 * self.metallic;
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let (u, v) = (rng.next_f32(), rng.next_f32());
+        let light = if rng.next_f32() < specular_probability {
+            //Half vector distributed like D(h) cos(h), reflecting the view around it
+            let alpha_squared = self.alpha * self.alpha;
+            let cos_theta = ((1.0 - u) / (1.0 + (alpha_squared - 1.0) * u)).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * v;
+            let half = tangent * (sin_theta * phi.cos())
+                + bitangent * (sin_theta * phi.sin())
+                + normal * cos_theta;
+            half * (2.0 * cgmath::dot(*view, half)) - view
+        } else {
+            let (x, y, z) = cosine_hemisphere(u, v);
+            tangent * x + bitangent * y + normal * z
+        };
+        let normal_dot_light = cgmath::dot(*normal, light);
+        if normal_dot_light <= 0.0 {
+            return None;
+        }
+        let half = (view + light).normalize();
+        let specular_density = self.distribution(cgmath::dot(*normal, half))
+            * cgmath::dot(*normal, half)
+            / (4.0 * cgmath::dot(*view, half).max(f32::EPSILON));
+        let diffuse_density = normal_dot_light / PI;
+        let density = specular_probability * specular_density
+            + (1.0 - specular_probability) * diffuse_density;
+        if density <= 0.0 {
+            return None;
+        }
+        let weight = self.evaluate(normal, view, &light) * (normal_dot_light / density);
+        Some((light, weight))
+    }
+
+    //GGX normal distribution
+    fn distribution(&self, normal_dot_half: f32) -> f32 {
+        let alpha_squared = self.alpha * self.alpha;
+        let denominator = normal_dot_half * normal_dot_half * (alpha_squared - 1.0) + 1.0;
+        alpha_squared / (PI * denominator * denominator)
+    }
+
+    //Height correlated Smith masking-shadowing, already divided by 4 (n.v) (n.l)
+    fn visibility(&self, normal_dot_view: f32, normal_dot_light: f32) -> f32 {
+        let alpha_squared = self.alpha * self.alpha;
+        let view_term = normal_dot_light
+            * (normal_dot_view * normal_dot_view * (1.0 - alpha_squared) + alpha_squared).sqrt();
+        let light_term = normal_dot_view
+            * (normal_dot_light * normal_dot_light * (1.0 - alpha_squared) + alpha_squared).sqrt();
+        0.5 / (view_term + light_term)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Fraction of light arriving straight down that is reflected in any direction, integrated by Monte Carlo
+    fn albedo(brdf: &PbrBrdf) -> f32 {
+        let normal = cgmath::Vector3::new(0.0, 0.0, 1.0);
+        let mut rng = Rng::new(11);
+        let samples = 20000;
+        let mut total = 0.0;
+        for _ in 0..samples {
+            if let Some((_, weight)) = brdf.sample(&normal, &normal, &mut rng) {
+                total += weight.g;
+            }
+        }
+        total / samples as f32
+    }
+
+    #[test]
+    fn brdf_does_not_create_energy() {
+        let white = Color::<u8> {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+        //Single scattering GGX loses energy as it gets rougher, at roughness 1 a metal keeps about 30%
+        let cases = [
+            (0.0, 0.2, 0.9),
+            (0.0, 0.9, 0.8),
+            (1.0, 0.3, 0.9),
+            (1.0, 1.0, 0.25),
+        ];
+        for &(metallic, roughness, at_least) in cases.iter() {
+            let reflected = albedo(&PbrBrdf::new(white.to_linear(), metallic, roughness));
+            assert!(
+                reflected <= 1.02,
+                "{} {} {}",
+                metallic,
+                roughness,
+                reflected
+            );
+            assert!(
+                reflected >= at_least,
+                "{} {} {}",
+                metallic,
+                roughness,
+                reflected
+            );
+        }
+    }
+
+    #[test]
+    fn brdf_is_reciprocal() {
+        let brdf = PbrBrdf::new(
+            Color::<u8> {
+                r: 200,
+                g: 120,
+                b: 40,
+                a: 255,
+            }
+            .to_linear(),
+            0.3,
+            0.5,
+        );
+        let normal = cgmath::Vector3::new(0.0, 1.0, 0.0);
+        let view = cgmath::Vector3::new(0.3, 0.8, 0.1).normalize();
+        let light = cgmath::Vector3::new(-0.6, 0.5, 0.2).normalize();
+        let forward = brdf.evaluate(&normal, &view, &light);
+        let backward = brdf.evaluate(&normal, &light, &view);
+        assert!((forward.r - backward.r).abs() < 1e-5);
+        assert!((forward.b - backward.b).abs() < 1e-5);
+    }
+}
@@ -1,3 +1,8 @@
+use crate::shape::HitRecord;
+use crate::textures::ColorSource;
+use crate::textures::Encoding;
+use crate::textures::NormalMap;
+use crate::textures::TextureMap;
 use serde::{Deserialize, Serialize};
 use std::ops::Add;
 use std::ops::Mul;
@@ -9,6 +14,13 @@ pub static BG_COLOR: Color<u8> = Color::<u8> {
     a: 255,
 };
 
+pub static BLACK: Color<f32> = Color::<f32> {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.0,
+};
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 #[repr(C)]
 pub struct Color<T> {
@@ -36,6 +48,58 @@ impl Color<u8> {
             a: self.a,
         })
     }
+
+    //Decodes the sRGB color channels to linear light on a 0 - 1 scale, alpha is already linear
+    pub fn to_linear(self) -> Color<f32> {
+        Color::<f32> {
+            r: srgb_to_linear(self.r as f32 / u8::MAX as f32),
+            g: srgb_to_linear(self.g as f32 / u8::MAX as f32),
+            b: srgb_to_linear(self.b as f32 / u8::MAX as f32),
+            a: self.a as f32 / u8::MAX as f32,
+        }
+    }
+
+    //Scales every channel to 0 - 1 as stored, for values that aren't colors
+    pub fn to_unit(self) -> Color<f32> {
+        Color::<f32> {
+            r: self.r as f32 / u8::MAX as f32,
+            g: self.g as f32 / u8::MAX as f32,
+            b: self.b as f32 / u8::MAX as f32,
+            a: self.a as f32 / u8::MAX as f32,
+        }
+    }
+}
+
+impl Color<f32> {
+    //Encodes linear light as 8 bit sRGB, anything brighter than 1 is clamped
+    pub fn to_srgb8(self) -> Color<u8> {
+        let to_byte =
+            |linear: f32| (linear_to_srgb(linear.clamp(0.0, 1.0)) * u8::MAX as f32).round() as u8;
+        Color::<u8> {
+            r: to_byte(self.r),
+            g: to_byte(self.g),
+            b: to_byte(self.b),
+            a: u8::MAX,
+        }
+    }
+}
+
+//sRGB electro-optical transfer function, from encoded values to linear light
+pub fn srgb_to_linear(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+//Inverse of srgb_to_linear, expects values between 0 and 1
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
 }
 
 impl Add for Color<u8> {
@@ -129,14 +193,249 @@ impl Mul<f32> for Color<u8> {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Material {
     Matte {
-        color: Color<u8>,
+        color: ColorSource,
+        #[serde(default)]
+        normal_map: Option<NormalMap>,
     },
     Specular {
-        color: Color<u8>,
+        color: ColorSource,
         specular: f32,
         reflectiveness: f32,
+        //Greyscale map scaling reflectiveness across the surface, read as data like the metallic-roughness texture
+        #[serde(default)]
+        specular_texture: Option<TextureMap>,
+        #[serde(default)]
+        normal_map: Option<NormalMap>,
+    },
+    //Transparent material like glass or water, color tints the light passing through
+    Dielectric {
+        color: ColorSource,
+        //1.0 is air, glass is around 1.5 and water 1.33
+        refractive_index: f32,
+        #[serde(default)]
+        normal_map: Option<NormalMap>,
+    },
+    //Physically based metallic-roughness material, both parameters go from 0 to 1
+    Pbr {
+        base_color: ColorSource,
+        metallic: f32,
+        roughness: f32,
+        //Packed like glTF, roughness in the green channel and metallic in the blue, multiplied with the values above
+        #[serde(default)]
+        metallic_roughness_texture: Option<TextureMap>,
+        #[serde(default)]
+        normal_map: Option<NormalMap>,
     },
+    //Glows with color scaled by strength, the object becomes a light source that lights the rest of the scene
+    Emissive {
+        color: ColorSource,
+        strength: f32,
+    },
+}
+
+impl Material {
+    //Linear color of the surface, see ColorSource::get_color
+    pub fn get_color(&self) -> Color<f32> {
+        match self {
+            Material::Matte { color, .. } => color.get_color(),
+            Material::Specular { color, .. } => color.get_color(),
+            Material::Dielectric { color, .. } => color.get_color(),
+            Material::Pbr { base_color, .. } => base_color.get_color(),
+            Material::Emissive { color, .. } => color.get_color(),
+        }
+    }
+
+    //Linear radiance given off by the surface, black for everything but emissive materials
+    pub fn get_emission(&self) -> Color<f32> {
+        match self {
+            Material::Emissive { color, strength } => color.get_color() * *strength,
+            _ => BLACK,
+        }
+    }
+
+    //Normal to shade the hit with, bent by the material's normal map if it has one
+    pub fn shading_normal(&self, hit: &HitRecord) -> cgmath::Vector3<f32> {
+        match self {
+            Material::Matte {
+                normal_map: Some(normal_map),
+                ..
+            }
+            | Material::Specular {
+                normal_map: Some(normal_map),
+                ..
+            }
+            | Material::Dielectric {
+                normal_map: Some(normal_map),
+                ..
+            }
+            | Material::Pbr {
+                normal_map: Some(normal_map),
+                ..
+            } => normal_map.perturb(hit),
+            _ => hit.normal,
+        }
+    }
+
+    //The material with its textures looked up at the surface coordinates and point of a hit, leaving only flat values for shading
+    //The normal map is dropped too, it is applied to the hit through shading_normal instead
+    pub fn at(&self, uv: &cgmath::Vector2<f32>, point: &cgmath::Vector3<f32>) -> Material {
+        let flat = |color: &ColorSource| ColorSource::Linear(color.sample(uv, point));
+        match self {
+            Material::Matte { color, .. } => Material::Matte {
+                color: flat(color),
+                normal_map: None,
+            },
+            Material::Specular {
+                color,
+                specular,
+                reflectiveness,
+                specular_texture,
+                ..
+            } => Material::Specular {
+                color: flat(color),
+                specular: *specular,
+                reflectiveness: match specular_texture {
+                    Some(texture) => reflectiveness * texture.sample(uv, point, Encoding::Linear).r,
+                    None => *reflectiveness,
+                },
+                specular_texture: None,
+                normal_map: None,
+            },
+            Material::Dielectric {
+                color,
+                refractive_index,
+                ..
+            } => Material::Dielectric {
+                color: flat(color),
+                refractive_index: *refractive_index,
+                normal_map: None,
+            },
+            Material::Pbr {
+                base_color,
+                metallic,
+                roughness,
+                metallic_roughness_texture,
+                ..
+            } => {
+                let (metallic_scale, roughness_scale) = match metallic_roughness_texture {
+                    Some(texture) => {
+                        let texel = texture.sample(uv, point, Encoding::Linear);
+                        (texel.b, texel.g)
+                    }
+                    None => (1.0, 1.0),
+                };
+                Material::Pbr {
+                    base_color: flat(base_color),
+                    metallic: metallic * metallic_scale,
+                    roughness: roughness * roughness_scale,
+                    metallic_roughness_texture: None,
+                    normal_map: None,
+                }
+            }
+            Material::Emissive { color, strength } => Material::Emissive {
+                color: flat(color),
+                strength: *strength,
+            },
+        }
+    }
+
+    //Every texture the material uses, so their images can be loaded
+    pub fn textures_mut(&mut self) -> Vec<&mut TextureMap> {
+        fn add_color<'a>(color: &'a mut ColorSource, textures: &mut Vec<&'a mut TextureMap>) {
+            if let ColorSource::Texture(texture) = color {
+                textures.push(texture);
+            }
+        }
+        let mut textures = Vec::new();
+        match self {
+            Material::Matte { color, normal_map } => {
+                add_color(color, &mut textures);
+                textures.extend(normal_map.as_mut().map(NormalMap::texture_mut));
+            }
+            Material::Specular {
+                color,
+                specular_texture,
+                normal_map,
+                ..
+            } => {
+                add_color(color, &mut textures);
+                textures.extend(specular_texture.as_mut());
+                textures.extend(normal_map.as_mut().map(NormalMap::texture_mut));
+            }
+            Material::Dielectric {
+                color, normal_map, ..
+            } => {
+                add_color(color, &mut textures);
+                textures.extend(normal_map.as_mut().map(NormalMap::texture_mut));
+            }
+            Material::Pbr {
+                base_color,
+                metallic_roughness_texture,
+                normal_map,
+                ..
+            } => {
+                add_color(base_color, &mut textures);
+                textures.extend(metallic_roughness_texture.as_mut());
+                textures.extend(normal_map.as_mut().map(NormalMap::texture_mut));
+            }
+            Material::Emissive { color, .. } => add_color(color, &mut textures),
+        }
+        textures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips_through_linear() {
+        for value in 0..=u8::MAX {
+            let color = Color::<u8> {
+                r: value,
+                g: value,
+                b: value,
+                a: u8::MAX,
+            };
+            let round_trip = color.to_linear().to_srgb8();
+            assert_eq!(
+                (round_trip.r, round_trip.g, round_trip.b),
+                (value, value, value)
+            );
+        }
+    }
+
+    #[test]
+    fn specular_texture_scales_reflectiveness_by_its_stored_value() {
+        //Half grey, as close to 0.5 as a byte gets, which the sRGB decode would turn into about 0.22
+        let grey = Color::<u8> {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: u8::MAX,
+        };
+        let material = Material::Specular {
+            color: ColorSource::Solid(grey),
+            specular: 10.0,
+            reflectiveness: 0.8,
+            specular_texture: Some(TextureMap::Checker {
+                even: grey,
+                odd: grey,
+                scale: 1.0,
+            }),
+            normal_map: None,
+        };
+        match material.at(
+            &cgmath::Vector2::new(0.3, 0.6),
+            &cgmath::Vector3::new(0.0, 0.0, 0.0),
+        ) {
+            Material::Specular { reflectiveness, .. } => {
+                assert!((reflectiveness - 0.8 * 128.0 / 255.0).abs() < 1e-4)
+            }
+            _ => panic!("Expected a specular material"),
+        }
+    }
 }
@@ -1,6 +1,23 @@
+use crate::clipping::clip_line;
+use crate::clipping::clip_polygon;
+use crate::clipping::clip_segment;
+use crate::clipping::ClipVertex;
+use crate::depth_buffer::DepthBuffer;
+use crate::lights::Attenuation;
+use crate::lights::Light;
 use crate::properties::Color;
+use crate::properties::Material;
 use crate::properties::BG_COLOR;
-use crate::state::Surface;
+use crate::properties::BLACK;
+use crate::ray_tracer::ambient_reflectance;
+use crate::ray_tracer::calculate_directional_light;
+use crate::ray_tracer::spot_cone_factor;
+use crate::ray_tracer::unshadowed_area_light;
+use crate::scene::Scene;
+use crate::settings::Shading;
+use crate::surface::Surface;
+use cgmath::InnerSpace;
+use std::collections::HashSet;
 use std::mem;
 
 pub fn draw_line(
@@ -9,6 +26,19 @@ pub fn draw_line(
     color: Color<u8>,
     surface: &mut dyn Surface,
 ) {
+    //Only the part on the surface is drawn, so lines may start or end anywhere
+    let (start, end) = match clip_line(
+        start,
+        end,
+        cgmath::Vector2::new(0.0, 0.0),
+        cgmath::Vector2::new(
+            surface.get_width() as f32 - 1.0,
+            surface.get_height() as f32 - 1.0,
+        ),
+    ) {
+        Some(clipped) => clipped,
+        None => return,
+    };
     //Need to swap draw algorithm over to handle completely vertical and completely horizontal lines
     let run = end.x - start.x;
     let rise = end.y - start.y;
@@ -51,6 +81,180 @@ pub fn draw_line(
     }
 }
 
+//Fills the triangle with a single color
+//Vertices are in surface pixels with z the reciprocal of their depth, which is only looked at with a depth buffer
+pub fn draw_filled_triangle(
+    vertices: [cgmath::Vector3<f32>; 3],
+    color: Color<u8>,
+    surface: &mut dyn Surface,
+    mut depth_buffer: Option<&mut DepthBuffer>,
+) {
+    let size = (surface.get_width(), surface.get_height());
+    let screen = vertices.map(|vertex| vertex.truncate());
+    let depths = vertices.map(|vertex| [vertex.z]);
+    rasterize_triangle(screen, depths, size, |x, y, [inverse_depth]| {
+        if passes_depth_test(&mut depth_buffer, x, y, inverse_depth) {
+            surface.set_pixel(x, y, &color)
+        }
+    });
+}
+
+//Fills the triangle blending the colors given at its vertices (Gouraud shading)
+//Scale a single color by per vertex intensities for the flat colored, smoothly lit look
+pub fn draw_shaded_triangle(
+    vertices: [cgmath::Vector3<f32>; 3],
+    colors: [Color<u8>; 3],
+    surface: &mut dyn Surface,
+    mut depth_buffer: Option<&mut DepthBuffer>,
+) {
+    let size = (surface.get_width(), surface.get_height());
+    let screen = vertices.map(|vertex| vertex.truncate());
+    let attributes = [0, 1, 2].map(|corner| {
+        let color = colors[corner];
+        [
+            vertices[corner].z,
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        ]
+    });
+    rasterize_triangle(
+        screen,
+        attributes,
+        size,
+        |x, y, [inverse_depth, r, g, b, a]| {
+            if !passes_depth_test(&mut depth_buffer, x, y, inverse_depth) {
+                return;
+            }
+            let to_byte = |channel: f32| channel.round().clamp(0.0, 255.0) as u8;
+            surface.set_pixel(
+                x,
+                y,
+                &Color::<u8> {
+                    r: to_byte(r),
+                    g: to_byte(g),
+                    b: to_byte(b),
+                    a: to_byte(a),
+                },
+            )
+        },
+    );
+}
+
+//Without a depth buffer every pixel is drawn
+fn passes_depth_test(
+    depth_buffer: &mut Option<&mut DepthBuffer>,
+    x: u32,
+    y: u32,
+    inverse_depth: f32,
+) -> bool {
+    match depth_buffer {
+        Some(depth_buffer) => depth_buffer.test_and_write(x, y, inverse_depth),
+        None => true,
+    }
+}
+
+//Calls plot for every pixel of the surface whose center lies inside the triangle, with the vertex attributes interpolated there
+//Scanline conversion: x and the attributes are interpolated down the edges for every row, then across the row
+//Centers exactly on an edge follow the top-left rule (with rows counted up from y = 0), so triangles sharing an edge
+//never both draw a pixel nor leave a gap between them
+fn rasterize_triangle<const N: usize>(
+    vertices: [cgmath::Vector2<f32>; 3],
+    attributes: [[f32; N]; 3],
+    (width, height): (u32, u32),
+    mut plot: impl FnMut(u32, u32, [f32; N]),
+) {
+    let mut corners = [
+        (vertices[0], attributes[0]),
+        (vertices[1], attributes[1]),
+        (vertices[2], attributes[2]),
+    ];
+    corners.sort_by(|a, b| a.0.y.total_cmp(&b.0.y));
+    let [(p0, a0), (p1, a1), (p2, a2)] = corners;
+    //First pixel whose center is at or past the coordinate, clamped to the surface
+    let first_at =
+        |coordinate: f32, limit: u32| (coordinate - 0.5).ceil().clamp(0.0, limit as f32) as usize;
+    let first_row = first_at(p0.y, height);
+    let split_row = first_at(p1.y, height);
+    let end_row = first_at(p2.y, height);
+    if first_row >= end_row {
+        return;
+    }
+    //x followed by the attributes, interpolated along the edge for the pixel rows start..end
+    let edge = |from: &(cgmath::Vector2<f32>, [f32; N]),
+                to: &(cgmath::Vector2<f32>, [f32; N]),
+                start: usize,
+                end: usize|
+     -> Vec<Vec<f32>> {
+        if start >= end {
+            return vec![Vec::new(); N + 1];
+        }
+        let at_row = |row: usize, from_value: f32, to_value: f32| {
+            let t = (row as f32 + 0.5 - from.0.y) / (to.0.y - from.0.y);
+            from_value + (to_value - from_value) * t
+        };
+        let along = |from_value: f32, to_value: f32| {
+            interpolate(
+                start,
+                end - 1,
+                at_row(start, from_value, to_value),
+                at_row(end - 1, from_value, to_value),
+            )
+        };
+        let mut values = vec![along(from.0.x, to.0.x)];
+        for attribute in 0..N {
+            values.push(along(from.1[attribute], to.1[attribute]));
+        }
+        values
+    };
+    let long_side = edge(&(p0, a0), &(p2, a2), first_row, end_row);
+    let lower_side = edge(&(p0, a0), &(p1, a1), first_row, split_row);
+    let upper_side = edge(&(p1, a1), &(p2, a2), split_row, end_row);
+    for row in first_row..end_row {
+        let step = row - first_row;
+        //x followed by the attributes where the row crosses each side
+        let mut left: Vec<f32> = long_side.iter().map(|values| values[step]).collect();
+        let mut right: Vec<f32> = if row < split_row {
+            lower_side.iter().map(|values| values[step]).collect()
+        } else {
+            upper_side
+                .iter()
+                .map(|values| values[row - split_row])
+                .collect()
+        };
+        if left[0] > right[0] {
+            mem::swap(&mut left, &mut right);
+        }
+        let first_column = first_at(left[0], width);
+        let end_column = first_at(right[0], width);
+        if first_column >= end_column {
+            continue;
+        }
+        let at_column = |column: usize, left_value: f32, right_value: f32| {
+            let t = (column as f32 + 0.5 - left[0]) / (right[0] - left[0]);
+            left_value + (right_value - left_value) * t
+        };
+        let across: Vec<Vec<f32>> = (0..N)
+            .map(|attribute| {
+                interpolate(
+                    first_column,
+                    end_column - 1,
+                    at_column(first_column, left[attribute + 1], right[attribute + 1]),
+                    at_column(end_column - 1, left[attribute + 1], right[attribute + 1]),
+                )
+            })
+            .collect();
+        for column in first_column..end_column {
+            let mut values = [0.0; N];
+            for (attribute, value) in values.iter_mut().enumerate() {
+                *value = across[attribute][column - first_column];
+            }
+            plot(column as u32, row as u32, values);
+        }
+    }
+}
+
 pub fn clear_screen(surface: &mut dyn Surface) {
     for y in 0..surface.get_height() {
         for x in 0..surface.get_width() {
@@ -59,6 +263,260 @@ pub fn clear_screen(surface: &mut dyn Surface) {
     }
 }
 
+//Renders the scene's objects as triangles through the model, view and projection transforms
+//Lighting is computed at the vertices without shadows and blended across each triangle (Gouraud shading),
+//the depth buffer, which should match the surface in size, decides which triangle is in front at every pixel
+//as the scene's depth settings say
+pub fn draw_scene(scene: &Scene, surface: &mut dyn Surface, depth_buffer: &mut DepthBuffer) {
+    debug_assert_eq!(
+        (depth_buffer.get_width(), depth_buffer.get_height()),
+        (surface.get_width(), surface.get_height())
+    );
+    depth_buffer.set_test(scene.settings.depth_test);
+    depth_buffer.set_write(scene.settings.depth_write);
+    let width = surface.get_width() as f32;
+    let height = surface.get_height() as f32;
+    let view_projection =
+        scene.camera.projection_matrix(width / height) * scene.camera.view_matrix();
+    let tone_map = |color: Color<f32>| {
+        scene
+            .settings
+            .tone_mapping
+            .apply(color, scene.settings.exposure)
+            .to_srgb8()
+    };
+    for (object, tessellation) in scene.objects.iter().zip(scene.tessellations()) {
+        let material = object.get_material();
+        let model_view_projection = view_projection * tessellation.model;
+        let shade = |position: &cgmath::Vector3<f32>,
+                     normal: &cgmath::Vector3<f32>,
+                     uv: &cgmath::Vector2<f32>| {
+            shade_vertex(scene, position, normal, &material.at(uv, position))
+        };
+        //World space position and normal of every vertex, where the lighting is worked out
+        let world: Vec<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)> = tessellation
+            .vertices
+            .iter()
+            .map(|vertex| {
+                (
+                    (tessellation.model * vertex.position.extend(1.0)).truncate(),
+                    (tessellation.model * vertex.normal.extend(0.0))
+                        .truncate()
+                        .normalize(),
+                )
+            })
+            .collect();
+        //Smooth shading lights every vertex, flat shading lights each triangle on its own below
+        let vertex_colors: Vec<Color<f32>> = match scene.settings.shading {
+            Shading::Smooth => tessellation
+                .vertices
+                .iter()
+                .zip(world.iter())
+                .map(|(vertex, (position, normal))| shade(position, normal, &vertex.uv))
+                .collect(),
+            Shading::Flat => Vec::new(),
+        };
+        for indices in tessellation.indices.iter() {
+            let corners = indices.map(|index| index as usize);
+            //Clip space position with the shaded color, kept linear so it can be interpolated when clipping
+            let polygon = clip_polygon(&corners.map(|corner| -> ClipVertex<3> {
+                let color = vertex_colors.get(corner).copied().unwrap_or(BLACK);
+                (
+                    model_view_projection * tessellation.vertices[corner].position.extend(1.0),
+                    [color.r, color.g, color.b],
+                )
+            }));
+            if polygon.len() < 3 {
+                continue;
+            }
+            //Position on the surface with the reciprocal depth
+            let screen: Vec<cgmath::Vector3<f32>> = polygon
+                .iter()
+                .map(|(clip, _)| {
+                    cgmath::Vector3::new(
+                        (clip.x / clip.w + 1.0) / 2.0 * width,
+                        (clip.y / clip.w + 1.0) / 2.0 * height,
+                        1.0 / clip.w,
+                    )
+                })
+                .collect();
+            //Clipping a triangle leaves a convex polygon, drawn as a fan from its first vertex
+            let fans = (1..screen.len() - 1).map(|fan| [0, fan, fan + 1]);
+            match scene.settings.shading {
+                Shading::Smooth => {
+                    let colors: Vec<Color<u8>> = polygon
+                        .iter()
+                        .map(|(_, [r, g, b])| {
+                            tone_map(Color::<f32> {
+                                r: *r,
+                                g: *g,
+                                b: *b,
+                                a: 1.0,
+                            })
+                        })
+                        .collect();
+                    for fan in fans {
+                        draw_shaded_triangle(
+                            fan.map(|corner| screen[corner]),
+                            fan.map(|corner| colors[corner]),
+                            surface,
+                            Some(depth_buffer),
+                        );
+                    }
+                }
+                Shading::Flat => {
+                    let position = corners
+                        .iter()
+                        .fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, corner| {
+                            sum + world[*corner].0
+                        })
+                        / 3.0;
+                    let normal = corners
+                        .iter()
+                        .fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, corner| {
+                            sum + world[*corner].1
+                        })
+                        .normalize();
+                    let uv = corners
+                        .iter()
+                        .fold(cgmath::Vector2::new(0.0, 0.0), |sum, corner| {
+                            sum + tessellation.vertices[*corner].uv
+                        })
+                        / 3.0;
+                    let color = tone_map(shade(&position, &normal, &uv));
+                    for fan in fans {
+                        draw_filled_triangle(
+                            fan.map(|corner| screen[corner]),
+                            color,
+                            surface,
+                            Some(depth_buffer),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Linear color of the surface point lit by every light in the scene, like the ray tracer but without shadows
+fn shade_vertex(
+    scene: &Scene,
+    point: &cgmath::Vector3<f32>,
+    normal: &cgmath::Vector3<f32>,
+    material: &Material,
+) -> Color<f32> {
+    if let Material::Emissive { .. } = material {
+        return material.get_emission();
+    }
+    let view = scene.camera.position - point;
+    //Surfaces are lit from whichever side faces the camera, as a ray would hit them
+    let normal = if cgmath::dot(*normal, view) < 0.0 {
+        -*normal
+    } else {
+        *normal
+    };
+    let light_from = |to_light: &cgmath::Vector3<f32>, intensity: &Color<f32>| {
+        calculate_directional_light(to_light, intensity, &normal, material, &view)
+    };
+    let mut total = BLACK;
+    for light in scene.lights.iter() {
+        total = total
+            + match light {
+                Light::Directional {
+                    direction,
+                    intensity,
+                } => light_from(direction, intensity),
+                Light::Point {
+                    position,
+                    intensity,
+                    attenuation,
+                } => {
+                    let to_light = position - point;
+                    light_from(
+                        &to_light,
+                        &(*intensity * attenuation.factor(to_light.magnitude())),
+                    )
+                }
+                Light::Spot {
+                    position,
+                    direction,
+                    inner_angle,
+                    outer_angle,
+                    intensity,
+                    attenuation,
+                } => {
+                    let to_light = position - point;
+                    let falloff =
+                        spot_cone_factor(&-to_light, direction, *inner_angle, *outer_angle)
+                            * attenuation.factor(to_light.magnitude());
+                    light_from(&to_light, &(*intensity * falloff))
+                }
+                //A single point light in the middle of the shape
+                Light::Area {
+                    shape,
+                    intensity,
+                    attenuation,
+                    ..
+                } => {
+                    let to_light = shape.sample(0.5, 0.5, point) - point;
+                    light_from(
+                        &to_light,
+                        &(*intensity * attenuation.factor(to_light.magnitude())),
+                    )
+                }
+                Light::Ambient { intensity } => ambient_reflectance(material) * *intensity,
+            };
+    }
+    for emitter in scene.emitters() {
+        if let Some((to_light, intensity)) = unshadowed_area_light(
+            &emitter.shape,
+            &emitter.radiance,
+            &Attenuation::default(),
+            (0.5, 0.5),
+            point,
+        ) {
+            total = total + light_from(&to_light, &intensity);
+        }
+    }
+    total
+}
+
+//Edges of the triangles draw_scene fills, in the color of each object's material, to inspect the tessellation
+pub fn draw_scene_wireframe(scene: &Scene, surface: &mut dyn Surface) {
+    let width = surface.get_width() as f32;
+    let height = surface.get_height() as f32;
+    let view_projection =
+        scene.camera.projection_matrix(width / height) * scene.camera.view_matrix();
+    let to_screen = |clip: cgmath::Vector4<f32>| {
+        cgmath::Vector2::new(
+            (clip.x / clip.w + 1.0) / 2.0 * width,
+            (clip.y / clip.w + 1.0) / 2.0 * height,
+        )
+    };
+    for (object, tessellation) in scene.objects.iter().zip(scene.tessellations()) {
+        let model_view_projection = view_projection * tessellation.model;
+        let clip: Vec<cgmath::Vector4<f32>> = tessellation
+            .vertices
+            .iter()
+            .map(|vertex| model_view_projection * vertex.position.extend(1.0))
+            .collect();
+        let color = object.get_material().get_color().to_srgb8();
+        //Neighbouring triangles share their edges, which are only drawn once
+        let mut drawn_edges = HashSet::new();
+        for indices in tessellation.indices.iter() {
+            for corner in 0..indices.len() {
+                let (from, to) = (indices[corner], indices[(corner + 1) % indices.len()]);
+                if !drawn_edges.insert((from.min(to), from.max(to))) {
+                    continue;
+                }
+                if let Some((start, end)) = clip_segment(clip[from as usize], clip[to as usize]) {
+                    draw_line(to_screen(start), to_screen(end), color, surface);
+                }
+            }
+        }
+    }
+}
+
 pub fn interpolate(
     independent_start: usize,
     independent_end: usize,
@@ -88,6 +546,7 @@ pub fn interpolate(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::depth_buffer::DepthTest;
 
     #[test]
     fn interpolate_single_value() {
@@ -112,4 +571,188 @@ mod tests {
         assert_eq!(values[3], starting_y + (expected_gradient * 3.0));
         assert_eq!(values[4], end_y);
     }
+
+    //Counts how often every pixel is drawn and keeps the last color
+    struct CountingSurface {
+        width: u32,
+        height: u32,
+        counts: Vec<u32>,
+        colors: Vec<Color<u8>>,
+    }
+
+    impl CountingSurface {
+        fn new(width: u32, height: u32) -> Self {
+            Self {
+                width,
+                height,
+                counts: vec![0; (width * height) as usize],
+                colors: vec![BG_COLOR; (width * height) as usize],
+            }
+        }
+    }
+
+    impl Surface for CountingSurface {
+        fn set_pixel(&mut self, x: u32, y: u32, color: &Color<u8>) {
+            let index = (x + y * self.width) as usize;
+            self.counts[index] += 1;
+            self.colors[index] = *color;
+        }
+
+        fn get_width(&self) -> u32 {
+            self.width
+        }
+
+        fn get_height(&self) -> u32 {
+            self.height
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_edges_cover_every_pixel_once() {
+        let point = |x: f32, y: f32| cgmath::Vector3::new(x, y, 1.0);
+        //Fan around a point sitting exactly on a pixel center, with edges running through many other centers
+        //and corners hanging off the surface
+        let center = point(4.5, 4.5);
+        let corners = [
+            point(-2.0, -2.0),
+            point(12.0, -2.0),
+            point(12.0, 12.0),
+            point(-2.0, 12.0),
+        ];
+        let mut surface = CountingSurface::new(10, 10);
+        for index in 0..corners.len() {
+            let next = corners[(index + 1) % corners.len()];
+            draw_filled_triangle([center, corners[index], next], BG_COLOR, &mut surface, None);
+        }
+        assert!(surface.counts.iter().all(|count| *count == 1));
+        //A square split along its diagonal, whose pixel centers lie on it
+        let mut surface = CountingSurface::new(10, 10);
+        let (a, b, c, d) = (
+            point(1.0, 1.0),
+            point(9.0, 1.0),
+            point(9.0, 9.0),
+            point(1.0, 9.0),
+        );
+        draw_filled_triangle([a, b, c], BG_COLOR, &mut surface, None);
+        draw_filled_triangle([a, c, d], BG_COLOR, &mut surface, None);
+        let drawn: Vec<u32> = surface
+            .counts
+            .iter()
+            .copied()
+            .filter(|count| *count > 0)
+            .collect();
+        assert_eq!(drawn.len(), 64);
+        assert!(drawn.iter().all(|count| *count == 1));
+    }
+
+    #[test]
+    fn lines_running_off_the_surface_are_clipped() {
+        let mut surface = CountingSurface::new(10, 10);
+        draw_line(
+            cgmath::Vector2::new(-50.0, 5.0),
+            cgmath::Vector2::new(60.0, 5.0),
+            BG_COLOR,
+            &mut surface,
+        );
+        //Cut to the last pixel center, which the line itself stops short of
+        assert!((0..9).all(|x| surface.counts[(x + 5 * 10) as usize] == 1));
+        //Entirely off to the side
+        draw_line(
+            cgmath::Vector2::new(-50.0, -5.0),
+            cgmath::Vector2::new(-20.0, 30.0),
+            BG_COLOR,
+            &mut surface,
+        );
+        assert_eq!(surface.counts.iter().sum::<u32>(), 9);
+    }
+
+    #[test]
+    fn shaded_triangle_blends_vertex_colors() {
+        let grey = |value: u8| Color::<u8> {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        };
+        let mut surface = CountingSurface::new(10, 10);
+        //Brightness goes up by 20 per pixel to the right
+        draw_shaded_triangle(
+            [
+                cgmath::Vector3::new(0.0, 0.0, 1.0),
+                cgmath::Vector3::new(10.0, 0.0, 1.0),
+                cgmath::Vector3::new(0.0, 10.0, 1.0),
+            ],
+            [grey(0), grey(200), grey(0)],
+            &mut surface,
+            None,
+        );
+        assert_eq!(surface.colors[0].r, 10);
+        assert_eq!(surface.colors[4].r, 90);
+        assert_eq!(surface.colors[3 + 5 * 10].r, 70);
+    }
+
+    #[test]
+    fn depth_buffer_keeps_the_nearest_surface() {
+        let grey = |value: u8| Color::<u8> {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        };
+        //A quad 2 units away over the left half and one tilted from 1 unit at the left to 4 at the right
+        let near_left = |depths: [f32; 3]| {
+            [
+                cgmath::Vector3::new(0.0, 0.0, 1.0 / depths[0]),
+                cgmath::Vector3::new(10.0, 0.0, 1.0 / depths[1]),
+                cgmath::Vector3::new(0.0, 10.0, 1.0 / depths[2]),
+            ]
+        };
+        let mut surface = CountingSurface::new(10, 10);
+        let mut depth_buffer = DepthBuffer::new(10, 10);
+        draw_filled_triangle(
+            near_left([2.0, 2.0, 2.0]),
+            grey(50),
+            &mut surface,
+            Some(&mut depth_buffer),
+        );
+        draw_filled_triangle(
+            near_left([1.0, 4.0, 1.0]),
+            grey(200),
+            &mut surface,
+            Some(&mut depth_buffer),
+        );
+        //1/z is linear across the screen, so the tilted one goes behind where it reaches 0.5, two thirds of the way across
+        assert_eq!(surface.colors[0].r, 200);
+        assert_eq!(surface.colors[6].r, 200);
+        assert_eq!(surface.colors[7].r, 50);
+        assert!((depth_buffer.get_depth(0, 0) - 1.0 / (1.0 - 0.75 * 0.05)).abs() < 1e-4);
+        //Drawing it again at the same depth only passes with CloserOrEqual, and without writes the buffer keeps its values
+        draw_filled_triangle(
+            near_left([1.0, 4.0, 1.0]),
+            grey(90),
+            &mut surface,
+            Some(&mut depth_buffer),
+        );
+        assert_eq!(surface.colors[0].r, 200);
+        depth_buffer.set_test(DepthTest::CloserOrEqual);
+        depth_buffer.set_write(false);
+        draw_filled_triangle(
+            near_left([1.0, 4.0, 1.0]),
+            grey(90),
+            &mut surface,
+            Some(&mut depth_buffer),
+        );
+        assert_eq!(surface.colors[0].r, 90);
+        depth_buffer.set_test(DepthTest::Always);
+        draw_filled_triangle(
+            near_left([9.0, 9.0, 9.0]),
+            grey(10),
+            &mut surface,
+            Some(&mut depth_buffer),
+        );
+        assert_eq!(surface.colors[0].r, 10);
+        assert!((depth_buffer.get_depth(7, 0) - 2.0).abs() < 1e-4);
+        depth_buffer.clear();
+        assert_eq!(depth_buffer.get_depth(7, 0), f32::INFINITY);
+    }
 }
@@ -1,51 +1,178 @@
+use crate::film::Film;
+use crate::lights::AreaShape;
+use crate::lights::Attenuation;
 use crate::lights::Light;
-use crate::objects::Sphere;
+use crate::path_tracer::trace_path;
+use crate::pbr::PbrBrdf;
 use crate::properties::Color;
 use crate::properties::Material;
 use crate::properties::BG_COLOR;
+use crate::properties::BLACK;
+use crate::sampling::pixel_samples;
+use crate::sampling::stratified_samples;
+use crate::sampling::Rng;
 use crate::scene::Scene;
-use crate::state::Surface;
+use crate::settings::Integrator;
+use crate::shape::HitRecord;
 use cgmath::InnerSpace; //Dot product and magnitude
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-static MIN_Z: f32 = 1.0;
-static REFLECTION_RECURSION_LIMIT: u32 = 3;
+//Shadow rays towards the emissive objects per shaded point, each one goes to a single emitter picked by power
+static EMITTER_SAMPLES: u32 = 16;
 
-pub fn ray_trace(scene: &Scene, surface: &mut dyn Surface) {
-    //Get bounds of drawing sruface
-    let viewport_width = surface.get_width() as f32;
-    let viewport_height = surface.get_height() as f32;
-    let size = cgmath::Vector2::<f32> {
-        x: viewport_width,
-        y: viewport_height,
-    };
-    let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
-    for y in 0..surface.get_height() {
-        for x in 0..surface.get_width() {
-            //Centering x and y gives us a camera view centered at 0,0,0, rather than having the far left of the view starting at 0,0,0
-            let centered_x = x as f32 - (viewport_width / 2.0);
-            let centered_y = y as f32 - (viewport_height / 2.0);
-            let direction = canvas_to_viewport(centered_x, centered_y, size);
-            let color = trace_ray(
-                &origin,
-                &direction,
-                1.0,
-                f32::INFINITY,
-                scene,
-                REFLECTION_RECURSION_LIMIT,
+//Frames are split into square tiles of this many pixels, which are handed out to the render threads
+static TILE_SIZE: u32 = 32;
+
+//Rectangle of the surface rendered as one unit of work
+#[derive(Debug, Copy, Clone)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+//Renders one pass of the scene and adds it to the film, the result does not depend on the thread count
+pub fn ray_trace(scene: &Scene, film: &mut Film, recursion_depth: u32, threads: usize) {
+    let width = film.get_width();
+    let height = film.get_height();
+    let pass = film.get_passes();
+    let tiles = split_into_tiles(width, height);
+
+    if threads <= 1 {
+        for tile in tiles.iter() {
+            let colors = render_tile(scene, tile, width, height, pass, recursion_depth);
+            write_tile(film, tile, &colors);
+        }
+    } else {
+        //Workers pull the next unrendered tile until none are left, and send the pixels back to be written to the film
+        let next_tile = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..threads.min(tiles.len()) {
+                let sender = sender.clone();
+                let next_tile = &next_tile;
+                let tiles = &tiles;
+                scope.spawn(move || loop {
+                    let tile_index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let tile = match tiles.get(tile_index) {
+                        Some(tile) => tile,
+                        None => break,
+                    };
+                    let colors = render_tile(scene, tile, width, height, pass, recursion_depth);
+                    if sender.send((tile_index, colors)).is_err() {
+                        break;
+                    }
+                });
+            }
+            //Drop our own sender so the receiver finishes once every worker has
+            drop(sender);
+            for (tile_index, colors) in receiver.iter() {
+                write_tile(film, &tiles[tile_index], &colors);
+            }
+        });
+    }
+    film.finish_pass();
+}
+
+fn split_into_tiles(width: u32, height: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+//Returns the colors of the tile row by row
+fn render_tile(
+    scene: &Scene,
+    tile: &Tile,
+    surface_width: u32,
+    surface_height: u32,
+    pass: u32,
+    recursion_depth: u32,
+) -> Vec<Color<f32>> {
+    let viewport_width = surface_width as f32;
+    let viewport_height = surface_height as f32;
+    let aspect_ratio = viewport_width / viewport_height;
+    let origin = scene.camera.position;
+    let settings = &scene.settings;
+    let mut colors = Vec::with_capacity((tile.width * tile.height) as usize);
+    for y in tile.y..tile.y + tile.height {
+        for x in tile.x..tile.x + tile.width {
+            //Seeding per pixel keeps jittered samples identical whatever thread renders the tile
+            let mut rng = Rng::for_pixel(x, y, pass);
+            let samples = pixel_samples(
+                settings.sample_pattern,
+                settings.samples_per_pixel,
+                &settings.filter,
+                &mut rng,
             );
-            surface.set_pixel(x, y, &color);
+            let mut weighted_sum = BLACK;
+            let mut unweighted_sum = BLACK;
+            let mut total_weight = 0.0;
+            for (offset_x, offset_y) in samples.iter() {
+                let (device_x, device_y) = canvas_to_device(
+                    x as f32 + 0.5 + offset_x,
+                    y as f32 + 0.5 + offset_y,
+                    viewport_width,
+                    viewport_height,
+                );
+                let direction = scene.camera.ray_direction(device_x, device_y, aspect_ratio);
+                let color = match settings.integrator {
+                    Integrator::Whitted => trace_ray(
+                        &origin,
+                        &direction,
+                        1.0,
+                        f32::INFINITY,
+                        scene,
+                        recursion_depth,
+                        &mut rng,
+                    ),
+                    Integrator::PathTraced => {
+                        trace_path(&origin, &direction, scene, recursion_depth, &mut rng)
+                    }
+                };
+                let weight = settings.filter.evaluate(*offset_x, *offset_y);
+                weighted_sum = weighted_sum + color * weight;
+                unweighted_sum = unweighted_sum + color;
+                total_weight += weight;
+            }
+            //Negative lobes (Mitchell) can cancel out, fall back to a plain average rather than divide by zero
+            let average = if total_weight.abs() > f32::EPSILON {
+                weighted_sum * (1.0 / total_weight)
+            } else {
+                unweighted_sum * (1.0 / samples.len() as f32)
+            };
+            colors.push(average);
         }
     }
+    colors
 }
 
-fn canvas_to_viewport(x: f32, y: f32, size: cgmath::Vector2<f32>) -> cgmath::Vector3<f32> {
-    cgmath::Vector3::<f32> {
-        x: (x / size.x),
-        y: (y / size.y),
-        z: MIN_Z,
+fn write_tile(film: &mut Film, tile: &Tile, colors: &[Color<f32>]) {
+    for (index, color) in colors.iter().enumerate() {
+        let x = tile.x + index as u32 % tile.width;
+        let y = tile.y + index as u32 / tile.width;
+        film.add_sample(x, y, *color);
     }
 }
 
+//Maps a canvas position to normalized device coordinates, -1 to 1 across the surface centered on the camera
+fn canvas_to_device(x: f32, y: f32, width: f32, height: f32) -> (f32, f32) {
+    (2.0 * x / width - 1.0, 2.0 * y / height - 1.0)
+}
+
 //min and max distance are measured as the parameter t in the vector equation P = Q + t(V - Q), where V and Q are 2 points
 fn trace_ray(
     origin: &cgmath::Vector3<f32>,
@@ -54,23 +181,71 @@ fn trace_ray(
     max_distance: f32,
     scene: &Scene,
     reflection_recursion_depth: u32,
-) -> Color<u8> {
-    let (closest_sphere, closest_t) =
-        closest_intersection(origin, ray_direction, scene, min_distance, max_distance);
-    match closest_sphere {
-        Some(sphere) => {
-            let intersection = origin + closest_t * ray_direction;
-            let normal = (intersection - sphere.center).normalize();
-            let mut local_color = sphere.get_color()
-                * compute_lighting(
-                    scene,
-                    &intersection,
-                    &normal,
-                    &sphere.material,
-                    &(ray_direction * -1.0),
-                );
+    rng: &mut Rng,
+) -> Color<f32> {
+    match scene.intersect(origin, ray_direction, min_distance, max_distance) {
+        Some(mut hit) => {
+            let shading_normal = hit.material.shading_normal(&hit);
+            hit.set_shading_normal(shading_normal);
+            let intersection = hit.point;
+            let normal = hit.normal;
+            //Textures are looked up once here, everything below shades with flat values
+            let resolved = hit.material.at(&hit.uv, &hit.point);
+            let material = &resolved;
+            if let Material::Emissive { .. } = material {
+                return material.get_emission();
+            }
+            if reflection_recursion_depth > 0 {
+                if let Material::Dielectric {
+                    color,
+                    refractive_index,
+                    ..
+                } = material
+                {
+                    return trace_dielectric(
+                        &hit,
+                        ray_direction,
+                        &color.get_color(),
+                        *refractive_index,
+                        scene,
+                        reflection_recursion_depth - 1,
+                        rng,
+                    );
+                }
+            }
+            let mut local_color = compute_lighting(
+                scene,
+                &intersection,
+                &normal,
+                material,
+                &(ray_direction * -1.0),
+                rng,
+            );
             if reflection_recursion_depth > 0 {
-                if let Material::Specular { reflectiveness, .. } = sphere.material {
+                if let Material::Pbr {
+                    base_color,
+                    metallic,
+                    roughness,
+                    ..
+                } = material
+                {
+                    //A single mirror ray can't show blurry reflections, so they fade out as the surface gets rougher
+                    let brdf = PbrBrdf::new(base_color.get_color(), *metallic, *roughness);
+                    let reversed_ray = (ray_direction * -1.0).normalize();
+                    let smoothness = (1.0 - brdf.get_roughness()).powi(2);
+                    let weight = brdf.fresnel(cgmath::dot(normal, reversed_ray)) * smoothness;
+                    let reflected_color = trace_ray(
+                        &intersection,
+                        &reflect_ray(&normal, &reversed_ray),
+                        0.0001,
+                        f32::INFINITY,
+                        scene,
+                        reflection_recursion_depth - 1,
+                        rng,
+                    );
+                    local_color = local_color + reflected_color * weight;
+                }
+                if let Material::Specular { reflectiveness, .. } = *material {
                     //Compute reflected colors
                     let reversed_ray = ray_direction * -1.0;
                     let reflected_ray = reflect_ray(&normal, &reversed_ray);
@@ -81,6 +256,7 @@ fn trace_ray(
                         f32::INFINITY,
                         scene,
                         reflection_recursion_depth - 1,
+                        rng,
                     );
                     local_color =
                         local_color * (1.0 - reflectiveness) + reflected_color * reflectiveness;
@@ -88,62 +264,71 @@ fn trace_ray(
             }
             local_color
         }
-        None => BG_COLOR,
+        None => BG_COLOR.to_linear(),
     }
 }
 
-fn closest_intersection<'scene_lifetime>(
-    origin: &cgmath::Vector3<f32>,
-    direction: &cgmath::Vector3<f32>,
-    scene: &'scene_lifetime Scene,
-    min_distance: f32,
-    max_distance: f32,
-) -> (Option<&'scene_lifetime Sphere>, f32) {
-    let mut closest_t = f32::INFINITY;
-    let mut closest_sphere: Option<&Sphere> = None;
-    for sphere in scene.objects.iter() {
-        let determinants = intersect_ray_sphere(origin, direction, sphere);
-        if (min_distance..max_distance).contains(&determinants.0) && determinants.0 < closest_t {
-            closest_t = determinants.0;
-            closest_sphere = Some(&sphere);
-        }
-
-        if (min_distance..max_distance).contains(&determinants.1) && determinants.1 < closest_t {
-            closest_t = determinants.1;
-            closest_sphere = Some(&sphere);
-        }
-    }
-    (closest_sphere, closest_t)
-}
-
-//returns the determinants of the quadratic equation, f32::INFINITY(no intersection), both determinants equal (tangent), two solutions (intersection)
-fn intersect_ray_sphere(
-    origin: &cgmath::Vector3<f32>,
+//Splits the ray into a reflected and a refracted part weighted by the Fresnel term, once out of bounces it is lit like a matte surface
+fn trace_dielectric(
+    hit: &HitRecord,
     ray_direction: &cgmath::Vector3<f32>,
-    sphere: &Sphere,
-) -> (f32, f32) {
-    let radius = sphere.radius;
-    let origin_sphere = origin - sphere.center;
-    //Quadratic equation
-    let a = cgmath::dot(*ray_direction, *ray_direction);
-    let b = 2.0 * cgmath::dot(origin_sphere, *ray_direction);
-    let c = cgmath::dot(origin_sphere, origin_sphere) - radius * radius;
-    let discriminant = b * b - 4.0 * a * c;
-    if discriminant < 0.0 {
-        (f32::INFINITY, f32::INFINITY)
+    tint: &Color<f32>,
+    refractive_index: f32,
+    scene: &Scene,
+    recursion_depth: u32,
+    rng: &mut Rng,
+) -> Color<f32> {
+    let direction = ray_direction.normalize();
+    //Ratio of the refractive index we come from over the one we go into
+    let eta = if hit.front_face {
+        1.0 / refractive_index
     } else {
-        let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
-        let t2 = (-b - discriminant.sqrt()) / (2.0 * a);
-        (t1, t2)
+        refractive_index
+    };
+    let cos_incident = cgmath::dot(-direction, hit.normal).min(1.0);
+    let reflected_ray = reflect_ray(&hit.normal, &-direction);
+    let reflected_color = trace_ray(
+        &hit.point,
+        &reflected_ray,
+        0.0001,
+        f32::INFINITY,
+        scene,
+        recursion_depth,
+        rng,
+    );
+    match refract_ray(&direction, &hit.normal, eta) {
+        Some(refracted_ray) => {
+            //Schlick's approximation has to use the angle on the less dense side
+            let cos_outer = if eta > 1.0 {
+                cgmath::dot(refracted_ray, -hit.normal)
+            } else {
+                cos_incident
+            };
+            let reflectance = schlick_reflectance(cos_outer, refractive_index);
+            let refracted_color = trace_ray(
+                &hit.point,
+                &refracted_ray,
+                0.0001,
+                f32::INFINITY,
+                scene,
+                recursion_depth,
+                rng,
+            );
+            reflected_color * reflectance + *tint * refracted_color * (1.0 - reflectance)
+        }
+        //Total internal reflection
+        None => reflected_color,
     }
 }
 
+//Color the surface reflects towards the viewer from every light in the scene
 fn compute_lighting(
     scene: &Scene,
     intersection_point: &cgmath::Vector3<f32>,
     surface_normal: &cgmath::Vector3<f32>,
     material: &Material,
     view: &cgmath::Vector3<f32>,
+    rng: &mut Rng,
 ) -> Color<f32> {
     let mut total_intensity = Color::<f32> {
         r: 0.0,
@@ -160,8 +345,27 @@ fn compute_lighting(
                 material,
                 view,
                 scene,
+                rng,
             );
     }
+    //Emissive objects are sampled like area lights, but the samples are shared between all of them
+    if !scene.emitters().is_empty() {
+        for sample in stratified_samples(EMITTER_SAMPLES, rng) {
+            let pick = rng.next_f32();
+            if let Some((to_light, intensity)) =
+                sample_emitters(scene, pick, sample, intersection_point)
+            {
+                total_intensity = total_intensity
+                    + calculate_directional_light(
+                        &to_light,
+                        &(intensity * (1.0 / EMITTER_SAMPLES as f32)),
+                        surface_normal,
+                        material,
+                        view,
+                    );
+            }
+        }
+    }
     total_intensity
 }
 fn calculate_light_intensity(
@@ -171,6 +375,7 @@ fn calculate_light_intensity(
     material: &Material,
     view: &cgmath::Vector3<f32>,
     scene: &Scene,
+    rng: &mut Rng,
 ) -> Color<f32> {
     let mut light_intensity = Color::<f32> {
         r: 0.0,
@@ -197,45 +402,189 @@ fn calculate_light_intensity(
         Light::Point {
             position,
             intensity,
+            attenuation,
         } => {
             let direction = position - intersection_point;
-            if !point_in_shadow(intersection_point, &direction, 1.0, scene) {
+            let attenuation_factor = attenuation.factor(direction.magnitude());
+            if attenuation_factor > 0.0
+                && !point_in_shadow(intersection_point, &direction, 1.0, scene)
+            {
                 light_intensity = calculate_directional_light(
                     &direction,
-                    intensity,
+                    &(*intensity * attenuation_factor),
                     surface_normal,
                     material,
                     view,
                 )
             }
         }
-        Light::Ambient { intensity } => light_intensity = *intensity,
+        Light::Spot {
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+            intensity,
+            attenuation,
+        } => {
+            let to_light = position - intersection_point;
+            let falloff = spot_cone_factor(&-to_light, direction, *inner_angle, *outer_angle)
+                * attenuation.factor(to_light.magnitude());
+            if falloff > 0.0 && !point_in_shadow(intersection_point, &to_light, 1.0, scene) {
+                light_intensity = calculate_directional_light(
+                    &to_light,
+                    &(*intensity * falloff),
+                    surface_normal,
+                    material,
+                    view,
+                )
+            }
+        }
+        Light::Area {
+            shape,
+            intensity,
+            samples,
+            attenuation,
+        } => {
+            //Every sample is a point light carrying an even share of the intensity
+            let light_samples = stratified_samples(*samples, rng);
+            let share = 1.0 / light_samples.len() as f32;
+            for (u, v) in light_samples {
+                let to_light = shape.sample(u, v, intersection_point) - intersection_point;
+                let falloff = attenuation.factor(to_light.magnitude()) * share;
+                if falloff > 0.0 && !point_in_shadow(intersection_point, &to_light, 1.0, scene) {
+                    light_intensity = light_intensity
+                        + calculate_directional_light(
+                            &to_light,
+                            &(*intensity * falloff),
+                            surface_normal,
+                            material,
+                            view,
+                        );
+                }
+            }
+        }
+        Light::Ambient { intensity } => {
+            light_intensity = ambient_reflectance(material) * *intensity
+        }
     }
     light_intensity
 }
 
-fn point_in_shadow(
+//How much of a spot light reaches along from_light, smoothly going from 1 at the inner angle to 0 at the outer angle
+pub fn spot_cone_factor(
+    from_light: &cgmath::Vector3<f32>,
+    spot_direction: &cgmath::Vector3<f32>,
+    inner_angle: f32,
+    outer_angle: f32,
+) -> f32 {
+    let cos_angle = cgmath::dot(from_light.normalize(), spot_direction.normalize());
+    let cos_inner = inner_angle.to_radians().cos();
+    let cos_outer = outer_angle.to_radians().cos();
+    if cos_inner <= cos_outer {
+        //No fade region, a hard edged cone
+        return if cos_angle >= cos_outer { 1.0 } else { 0.0 };
+    }
+    let x = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+    //Smoothstep
+    x * x * (3.0 - 2.0 * x)
+}
+
+//Direction to the sample at (u, v) on the shape and the intensity of the light arriving from it, None when it is hidden
+pub fn sample_area_light(
+    shape: &AreaShape,
+    radiance: &Color<f32>,
+    attenuation: &Attenuation,
+    uv: (f32, f32),
+    point: &cgmath::Vector3<f32>,
+    scene: &Scene,
+) -> Option<(cgmath::Vector3<f32>, Color<f32>)> {
+    let (to_light, intensity) = unshadowed_area_light(shape, radiance, attenuation, uv, point)?;
+    //Stops just short of the light so the surface of an emissive object does not shadow itself
+    if point_in_shadow(point, &to_light, 0.999, scene) {
+        return None;
+    }
+    Some((to_light, intensity))
+}
+
+//Light reaching point from the emissive objects, estimated from one of them picked in proportion to its power
+//so the cost stays the same however many there are, like every triangle of an emissive mesh
+//pick chooses the emitter and uv the point on it, the result is scaled by one over the probability of the pick
+pub fn sample_emitters(
+    scene: &Scene,
+    pick: f32,
+    uv: (f32, f32),
+    point: &cgmath::Vector3<f32>,
+) -> Option<(cgmath::Vector3<f32>, Color<f32>)> {
+    let (emitter, probability) = scene.pick_emitter(pick)?;
+    let (to_light, intensity) = sample_area_light(
+        &emitter.shape,
+        &emitter.radiance,
+        &Attenuation::default(),
+        uv,
+        point,
+        scene,
+    )?;
+    Some((to_light, intensity * (1.0 / probability)))
+}
+
+//Like sample_area_light without the shadow ray, None only when the sample faces away from the point
+//The radiance L of the sample becomes the irradiance L * cos_light / (r^2 * pdf) it gives a surface facing it,
+//pdf being the density of the sample per unit area, divided by pi so it lights a diffuse surface like a point light would
+pub fn unshadowed_area_light(
+    shape: &AreaShape,
+    radiance: &Color<f32>,
+    attenuation: &Attenuation,
+    (u, v): (f32, f32),
+    point: &cgmath::Vector3<f32>,
+) -> Option<(cgmath::Vector3<f32>, Color<f32>)> {
+    let light_point = shape.sample(u, v, point);
+    let to_light = light_point - point;
+    let distance = to_light.magnitude();
+    let cos_light = cgmath::dot(shape.normal_at(&light_point, point), -to_light / distance);
+    if cos_light <= 0.0 {
+        return None;
+    }
+    let pdf = 1.0 / shape.sampled_area();
+    let irradiance = *radiance * (cos_light / (distance * distance * pdf));
+    Some((to_light, irradiance * (attenuation.factor(distance) / PI)))
+}
+
+pub fn point_in_shadow(
     intersection_point: &cgmath::Vector3<f32>,
     direction: &cgmath::Vector3<f32>,
     t_max: f32,
     scene: &Scene,
 ) -> bool {
     //Shadow check
-    let (shadow_sphere, _shadow_t) =
-        closest_intersection(intersection_point, direction, scene, 0.0001, t_max);
-    match shadow_sphere {
-        Some(_sphere) => true,
-        None => false,
-    }
+    scene.occluded(intersection_point, direction, 0.0001, t_max)
 }
 
-fn calculate_directional_light(
+//Light reflected towards the viewer from a light in direction with the given intensity
+pub fn calculate_directional_light(
     direction: &cgmath::Vector3<f32>,
     intensity: &Color<f32>,
     surface_normal: &cgmath::Vector3<f32>,
     material: &Material,
     view: &cgmath::Vector3<f32>,
 ) -> Color<f32> {
+    if let Material::Pbr {
+        base_color,
+        metallic,
+        roughness,
+        ..
+    } = material
+    {
+        let light = direction.normalize();
+        let cosine = cgmath::dot(*surface_normal, light);
+        if cosine <= 0.0 {
+            return BLACK;
+        }
+        //Times pi so a white diffuse surface is lit as brightly as a white matte one
+        let brdf = PbrBrdf::new(base_color.get_color(), *metallic, *roughness);
+        return brdf.evaluate(surface_normal, &view.normalize(), &light)
+            * *intensity
+            * (PI * cosine);
+    }
     let mut light_color_to_add = Color::<f32> {
         r: 0.0,
         g: 0.0,
@@ -259,15 +608,153 @@ fn calculate_directional_light(
             let specular_scale: f32 =
                 reflection_dot_view / (reflection.magnitude() * view.magnitude());
             light_color_to_add = light_color_to_add + *intensity * specular_scale.powf(*specular);
-            light_color_to_add.a = color.a as f32 / u8::MAX as f32;
+            light_color_to_add.a = color.get_color().a;
+        }
+    }
+    material.get_color() * light_color_to_add
+}
+
+//Fraction of ambient light a material sends back
+pub fn ambient_reflectance(material: &Material) -> Color<f32> {
+    match material {
+        Material::Pbr {
+            base_color,
+            metallic,
+            roughness,
+            ..
+        } => {
+            let brdf = PbrBrdf::new(base_color.get_color(), *metallic, *roughness);
+            brdf.diffuse_color() + brdf.specular_color()
         }
+        _ => material.get_color(),
     }
-    light_color_to_add
 }
 
-fn reflect_ray(
+pub fn reflect_ray(
     surface_normal: &cgmath::Vector3<f32>,
     ray: &cgmath::Vector3<f32>,
 ) -> cgmath::Vector3<f32> {
     2.0 * surface_normal * cgmath::dot(*surface_normal, *ray) - ray
 }
+
+//Snell's law for a unit direction and a unit normal facing against it, None on total internal reflection
+pub fn refract_ray(
+    direction: &cgmath::Vector3<f32>,
+    surface_normal: &cgmath::Vector3<f32>,
+    eta: f32,
+) -> Option<cgmath::Vector3<f32>> {
+    let cos_incident = cgmath::dot(-*direction, *surface_normal).min(1.0);
+    let sin_squared_transmitted = eta * eta * (1.0 - cos_incident * cos_incident);
+    if sin_squared_transmitted > 1.0 {
+        return None;
+    }
+    let cos_transmitted = (1.0 - sin_squared_transmitted).sqrt();
+    Some(eta * direction + (eta * cos_incident - cos_transmitted) * surface_normal)
+}
+
+//Fraction of light reflected at a boundary between air and the refractive index
+pub fn schlick_reflectance(cosine: f32, refractive_index: f32) -> f32 {
+    let r0 = ((1.0 - refractive_index) / (1.0 + refractive_index)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).max(0.0).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface::PixelSurface;
+
+    fn render(threads: usize) -> Vec<u8> {
+        let scene_json = std::fs::read_to_string("scene.json").unwrap();
+        let scene = Scene::load(&scene_json).unwrap();
+        //Odd size so the last row and column of tiles are partial
+        let mut film = Film::new(75, 45);
+        ray_trace(&scene, &mut film, 3, threads);
+        let mut surface = PixelSurface::new(75, 45);
+        film.develop(
+            &mut surface,
+            &scene.settings.tone_mapping,
+            scene.settings.exposure,
+        );
+        surface.get_pixels().to_vec()
+    }
+
+    #[test]
+    fn scene_can_be_shared_between_threads() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Scene>();
+    }
+
+    #[test]
+    fn integrators_agree_on_an_emissive_quad() {
+        //Two triangle quad light above a matte floor, with no other lights so only the emitters light the floor
+        let scene = Scene::load(
+            r#"{
+                "objects": [
+                    {"Plane": {
+                        "point": {"x": 0.0, "y": -1.0, "z": 0.0},
+                        "normal": {"x": 0.0, "y": 1.0, "z": 0.0},
+                        "material": {"Matte": {"color": [200, 200, 200, 255]}}
+                    }},
+                    {"Mesh": {
+                        "positions": [[-1.0, 1.0, 2.0], [1.0, 1.0, 2.0], [1.0, 1.0, 4.0], [-1.0, 1.0, 4.0]],
+                        "indices": [[0, 1, 2], [0, 2, 3]],
+                        "material": {"Emissive": {"color": [255, 255, 255, 255], "strength": 2.0}}
+                    }}
+                ],
+                "lights": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.emitters().len(), 2);
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let direction = cgmath::Vector3::new(0.3, -1.0, 3.0);
+        let samples = 4000;
+        let mut rng = Rng::new(3);
+        let (mut whitted, mut path_traced) = (BLACK, BLACK);
+        for _ in 0..samples {
+            whitted =
+                whitted + trace_ray(&origin, &direction, 1.0, f32::INFINITY, &scene, 0, &mut rng);
+            //No bounces, so the path tracer only adds the light sampled directly at the floor
+            path_traced = path_traced + trace_path(&origin, &direction, &scene, 0, &mut rng);
+        }
+        let (whitted, path_traced) = (whitted.r / samples as f32, path_traced.r / samples as f32);
+        assert!(whitted > 0.1);
+        assert!((whitted - path_traced).abs() < 0.03 * whitted);
+    }
+
+    #[test]
+    fn threaded_render_matches_single_threaded() {
+        assert_eq!(render(1), render(4));
+    }
+
+    #[test]
+    fn refraction_bends_towards_the_normal_and_reflects_internally() {
+        let normal = cgmath::Vector3::new(0.0, 1.0, 0.0);
+        let straight_down = cgmath::Vector3::new(0.0, -1.0, 0.0);
+        let through = refract_ray(&straight_down, &normal, 1.0 / 1.5).unwrap();
+        assert!((through - straight_down).magnitude() < 1e-6);
+
+        let slanted = cgmath::Vector3::new(1.0, -1.0, 0.0).normalize();
+        let bent = refract_ray(&slanted, &normal, 1.0 / 1.5).unwrap();
+        assert!(bent.x < slanted.x && (bent.magnitude() - 1.0).abs() < 1e-5);
+        //Leaving glass at 45 degrees is past the critical angle of about 42 degrees
+        assert!(refract_ray(&slanted, &normal, 1.5).is_none());
+
+        assert!((schlick_reflectance(1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((schlick_reflectance(0.0, 1.5) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn spot_light_fades_between_its_cone_angles() {
+        let down = cgmath::Vector3::new(0.0, -1.0, 0.0);
+        let at_angle = |degrees: f32| {
+            let radians = degrees.to_radians();
+            cgmath::Vector3::new(radians.sin(), -radians.cos(), 0.0)
+        };
+        assert_eq!(spot_cone_factor(&at_angle(0.0), &down, 20.0, 30.0), 1.0);
+        assert_eq!(spot_cone_factor(&at_angle(19.0), &down, 20.0, 30.0), 1.0);
+        let halfway = spot_cone_factor(&at_angle(25.0), &down, 20.0, 30.0);
+        assert!(halfway > 0.0 && halfway < 1.0);
+        assert_eq!(spot_cone_factor(&at_angle(31.0), &down, 20.0, 30.0), 0.0);
+    }
+}
@@ -0,0 +1,230 @@
+//Where the rays of a pixel are placed and how much each one counts towards the final color
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+//Small, fast PCG32 generator, seeded per pixel so renders are repeatable whatever thread draws them
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self {
+            state: seed.wrapping_add(0x853c_49e6_748f_ea9b),
+        };
+        rng.next_u32();
+        rng
+    }
+
+    //Seed derived from a pixel position and the pass being rendered, neighbouring pixels and passes get unrelated sequences
+    pub fn for_pixel(x: u32, y: u32, pass: u32) -> Self {
+        let mut seed =
+            (((y as u64) << 32) | x as u64) ^ (pass as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        //splitmix64 finalizer
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Self::new(seed ^ (seed >> 31))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rotation)
+    }
+
+    //Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum SamplePattern {
+    //Samples at the center of each cell of a grid over the filter area
+    Regular,
+    //One sample at a random spot inside each grid cell (stratified), trades aliasing for noise
+    Jittered,
+}
+
+//Reconstruction filter, weights a sample by its offset from the pixel center, radius is in pixels
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    Box { radius: f32 },
+    Tent { radius: f32 },
+    //alpha controls the falloff, larger is sharper
+    Gaussian { radius: f32, alpha: f32 },
+    //b and c of the Mitchell-Netravali family, 1/3 and 1/3 is the usual choice
+    Mitchell { radius: f32, b: f32, c: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius } => radius,
+            Filter::Tent { radius } => radius,
+            Filter::Gaussian { radius, .. } => radius,
+            Filter::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    //All filters are separable, the weight is the product of the filter along x and along y
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, offset: f32) -> f32 {
+        let offset = offset.abs();
+        if offset > self.radius() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - offset,
+            Filter::Gaussian { radius, alpha } => {
+                //Shifted down so the weight reaches zero at the edge instead of being cut off
+                ((-alpha * offset * offset).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                //The cubic is defined over [-2, 2], stretch it to fit the radius
+                let x = 2.0 * offset / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+//Sample offsets from the pixel center covering the filter area, count is rounded to the nearest square grid
+pub fn pixel_samples(
+    pattern: SamplePattern,
+    count: u32,
+    filter: &Filter,
+    rng: &mut Rng,
+) -> Vec<(f32, f32)> {
+    let grid_size = ((count as f32).sqrt().round() as u32).max(1);
+    let radius = filter.radius();
+    let cell_size = 2.0 * radius / grid_size as f32;
+    let mut samples = Vec::with_capacity((grid_size * grid_size) as usize);
+    for cell_y in 0..grid_size {
+        for cell_x in 0..grid_size {
+            let (jitter_x, jitter_y) = match pattern {
+                SamplePattern::Regular => (0.5, 0.5),
+                SamplePattern::Jittered => (rng.next_f32(), rng.next_f32()),
+            };
+            samples.push((
+                -radius + (cell_x as f32 + jitter_x) * cell_size,
+                -radius + (cell_y as f32 + jitter_y) * cell_size,
+            ));
+        }
+    }
+    samples
+}
+
+//One random point in each cell of a grid over the unit square, count is rounded to the nearest square grid
+pub fn stratified_samples(count: u32, rng: &mut Rng) -> Vec<(f32, f32)> {
+    let grid_size = ((count as f32).sqrt().round() as u32).max(1);
+    let cell_size = 1.0 / grid_size as f32;
+    let mut samples = Vec::with_capacity((grid_size * grid_size) as usize);
+    for cell_y in 0..grid_size {
+        for cell_x in 0..grid_size {
+            samples.push((
+                (cell_x as f32 + rng.next_f32()) * cell_size,
+                (cell_y as f32 + rng.next_f32()) * cell_size,
+            ));
+        }
+    }
+    samples
+}
+
+//Shirley and Chiu's mapping from the unit square to the unit disk, which keeps areas in proportion
+pub fn concentric_disk(u: f32, v: f32) -> (f32, f32) {
+    let x = 2.0 * u - 1.0;
+    let y = 2.0 * v - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+//Direction in the hemisphere around +z with probability proportional to its cosine with +z, for diffuse bounces
+pub fn cosine_hemisphere(u: f32, v: f32) -> (f32, f32, f32) {
+    let (x, y) = concentric_disk(u, v);
+    (x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_regular_sample_is_the_pixel_center() {
+        let mut rng = Rng::for_pixel(3, 4, 0);
+        let samples = pixel_samples(SamplePattern::Regular, 1, &Filter::default(), &mut rng);
+        assert_eq!(samples, vec![(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells() {
+        let mut rng = Rng::for_pixel(10, 20, 0);
+        let filter = Filter::Tent { radius: 1.0 };
+        let samples = pixel_samples(SamplePattern::Jittered, 16, &filter, &mut rng);
+        assert_eq!(samples.len(), 16);
+        for (index, (x, y)) in samples.iter().enumerate() {
+            let cell_x = (index % 4) as f32;
+            let cell_y = (index / 4) as f32;
+            assert!(*x >= -1.0 + cell_x * 0.5 && *x < -1.0 + (cell_x + 1.0) * 0.5);
+            assert!(*y >= -1.0 + cell_y * 0.5 && *y < -1.0 + (cell_y + 1.0) * 0.5);
+        }
+    }
+
+    #[test]
+    fn filters_vanish_outside_their_radius() {
+        let filters = [
+            Filter::Box { radius: 0.5 },
+            Filter::Tent { radius: 1.0 },
+            Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+        ];
+        for filter in filters.iter() {
+            assert!(filter.evaluate(0.0, 0.0) > 0.0);
+            assert_eq!(filter.evaluate(filter.radius() + 0.01, 0.0), 0.0);
+            //Everything but the box filter falls off smoothly towards the edge
+            if !matches!(filter, Filter::Box { .. }) {
+                assert!(filter.evaluate(filter.radius() * 0.999, 0.0).abs() < 1e-2);
+            }
+        }
+    }
+}
@@ -1,15 +1,46 @@
 //A scene is a description of objects that exist in the world, it handles loading of objects from a specified json file
-// right now objects are limited to spheres and lights
+// right now objects are limited to spheres, planes, triangles, meshes and lights, emissive objects double as lights
 
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::lights::AreaShape;
+use crate::lights::Emitter;
 use crate::lights::Light;
-use crate::objects::Sphere;
+use crate::objects::Object;
+use crate::properties::Material;
+use crate::settings::RenderSettings;
+use crate::shape::HitRecord;
+use crate::tessellation::tessellate;
+use crate::tessellation::Tessellation;
+use crate::textures::ColorSource;
+use crate::textures::ImageCache;
+use serde::de::Error;
 use serde::{Deserialize, Serialize};
 use serde_json::Result;
+use std::path::Path;
+use std::sync::OnceLock;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Scene {
-    pub objects: Vec<Sphere>,
+    pub objects: Vec<Object>,
     pub lights: Vec<Light>,
+    //Scenes without a camera are viewed from the origin down +z
+    #[serde(default)]
+    pub camera: Camera,
+    #[serde(default)]
+    pub settings: RenderSettings,
+    //Built from objects on load, call build_bvh again after changing objects
+    #[serde(skip)]
+    bvh: Bvh,
+    //Surfaces of the emissive objects, collected on load so they can be sampled as lights
+    #[serde(skip)]
+    emitters: Vec<Emitter>,
+    //Running total of the emitters' power, so lighting can pick one in proportion to how much light it gives off
+    #[serde(skip)]
+    emitter_power: Vec<f32>,
+    //Triangles the rasterizer draws for every object, made the first time it draws the scene and kept for later frames
+    #[serde(skip)]
+    tessellations: OnceLock<Vec<Tessellation>>,
 }
 
 impl Scene {
@@ -17,13 +48,197 @@ impl Scene {
         Self {
             objects: Vec::new(),
             lights: Vec::new(),
+            camera: Camera::default(),
+            settings: RenderSettings::default(),
+            bvh: Bvh::default(),
+            emitters: Vec::new(),
+            emitter_power: Vec::new(),
+            tessellations: OnceLock::new(),
         }
     }
 
     pub fn load(scene_description_json: &str) -> Result<Scene> {
-        let scene: Scene = serde_json::from_str(scene_description_json)?;
+        let mut scene: Scene = serde_json::from_str(scene_description_json)?;
+        for object in scene.objects.iter() {
+            //An infinite light can not be sampled, and would outshine everything else
+            if let Object::Plane(plane) = object {
+                if matches!(plane.material, Material::Emissive { .. }) {
+                    return Err(serde_json::Error::custom("Planes can not be emissive"));
+                }
+            }
+            //Lights are sampled with one color for the whole surface, which a texture would only match where it is hit directly
+            if let Material::Emissive {
+                color: ColorSource::Texture(_),
+                ..
+            } = object.get_material()
+            {
+                return Err(serde_json::Error::custom(
+                    "Emissive materials can not be textured",
+                ));
+            }
+        }
+        scene.build_bvh();
+        scene.collect_emitters();
         Ok(scene)
     }
 
+    //Meshes keep their own tree over their triangles, which Mesh::build_bvh rebuilds
+    //The rasterizer's tessellations are dropped too and made again the next time they are needed
+    pub fn build_bvh(&mut self) {
+        self.bvh = Bvh::build(&self.objects);
+        self.tessellations = OnceLock::new();
+    }
+
+    //Call again after changing objects, like build_bvh
+    pub fn collect_emitters(&mut self) {
+        self.emitters.clear();
+        self.emitter_power.clear();
+        for object in self.objects.iter() {
+            let radiance = match object {
+                Object::Sphere(sphere) => sphere.material.get_emission(),
+                Object::Triangle(triangle) => triangle.material.get_emission(),
+                Object::Mesh(mesh) => mesh.material.get_emission(),
+                Object::Plane(_) => continue,
+            };
+            if radiance.r <= 0.0 && radiance.g <= 0.0 && radiance.b <= 0.0 {
+                continue;
+            }
+            match object {
+                Object::Sphere(sphere) => self.emitters.push(Emitter {
+                    shape: AreaShape::Sphere {
+                        center: sphere.center,
+                        radius: sphere.radius,
+                    },
+                    radiance,
+                }),
+                Object::Triangle(triangle) => self.emitters.push(Emitter {
+                    shape: AreaShape::Triangle {
+                        a: triangle.a,
+                        b: triangle.b,
+                        c: triangle.c,
+                    },
+                    radiance,
+                }),
+                //Every triangle of the mesh is a separate light
+                Object::Mesh(mesh) => {
+                    for index in 0..mesh.triangle_count() {
+                        let [a, b, c] = mesh.triangle_positions(index);
+                        self.emitters.push(Emitter {
+                            shape: AreaShape::Triangle { a, b, c },
+                            radiance,
+                        });
+                    }
+                }
+                Object::Plane(_) => {}
+            }
+        }
+        let mut total_power = 0.0;
+        for emitter in self.emitters.iter() {
+            let radiance = emitter.radiance;
+            total_power +=
+                emitter.shape.sampled_area() * (radiance.r + radiance.g + radiance.b) / 3.0;
+            self.emitter_power.push(total_power);
+        }
+    }
+
+    //Decodes the images used by textures, relative paths are resolved against base_directory
+    pub fn load_images(&mut self, base_directory: &Path) -> anyhow::Result<()> {
+        let mut cache = ImageCache::new();
+        for object in self.objects.iter_mut() {
+            let material = match object {
+                Object::Sphere(sphere) => &mut sphere.material,
+                Object::Plane(plane) => &mut plane.material,
+                Object::Triangle(triangle) => &mut triangle.material,
+                Object::Mesh(mesh) => &mut mesh.material,
+            };
+            for texture in material.textures_mut() {
+                texture.load_images(base_directory, &mut cache)?;
+            }
+        }
+        Ok(())
+    }
+
+    //One per object in the same order, planes are laid out around the camera and refined near the lights
+    pub fn tessellations(&self) -> &[Tessellation] {
+        self.tessellations.get_or_init(|| {
+            self.objects
+                .iter()
+                .map(|object| tessellate(object, self))
+                .collect()
+        })
+    }
+
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    //Emitter picked by u in 0 to 1 in proportion to its power, along with the probability of picking it
+    pub fn pick_emitter(&self, u: f32) -> Option<(&Emitter, f32)> {
+        let total_power = *self.emitter_power.last()?;
+        if total_power <= 0.0 {
+            return None;
+        }
+        let index = self
+            .emitter_power
+            .partition_point(|power| *power <= u * total_power)
+            .min(self.emitters.len() - 1);
+        let previous_power = if index == 0 {
+            0.0
+        } else {
+            self.emitter_power[index - 1]
+        };
+        Some((
+            &self.emitters[index],
+            (self.emitter_power[index] - previous_power) / total_power,
+        ))
+    }
+
+    //Closest object along the ray, min and max distance are measured as the parameter t along origin + t * direction
+    pub fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        self.bvh
+            .intersect(&self.objects, origin, direction, min_distance, max_distance)
+    }
+
+    //Whether anything at all lies along the ray between min and max distance
+    pub fn occluded(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        self.bvh
+            .occluded(&self.objects, origin, direction, min_distance, max_distance)
+    }
+
     //pub fn save(scene: &Scene) -> Result<&str> {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_that_can_not_be_sampled_are_rejected() {
+        let scene_with =
+            |object: &str| Scene::load(&format!(r#"{{"objects": [{}], "lights": []}}"#, object));
+        assert!(scene_with(
+            r#"{"Sphere": {"center": [0, 0, 5], "radius": 1, "material": {"Emissive": {"color": [255, 255, 255, 255], "strength": 2}}}}"#
+        )
+        .is_ok());
+        assert!(scene_with(
+            r#"{"Plane": {"point": [0, 0, 0], "normal": [0, 1, 0], "material": {"Emissive": {"color": [255, 255, 255, 255], "strength": 2}}}}"#
+        )
+        .is_err());
+        assert!(scene_with(
+            r#"{"Sphere": {"center": [0, 0, 5], "radius": 1, "material": {"Emissive": {"color": {"Checker": {"even": [0, 0, 0, 255], "odd": [255, 255, 255, 255]}}, "strength": 2}}}}"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//Per scene options controlling how the scene is rendered, every field can be left out of the scene file
+use crate::depth_buffer::DepthTest;
+use crate::sampling::Filter;
+use crate::sampling::SamplePattern;
+use crate::tone_mapping::ToneMapping;
+use serde::{Deserialize, Serialize};
+
+//How the color seen along a camera ray is worked out
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum Integrator {
+    //Direct lighting with mirror reflections and refraction, ambient lights stand in for indirect light
+    #[default]
+    Whitted,
+    //Unbiased Monte Carlo path tracing with global illumination, noisy until many samples are accumulated
+    PathTraced,
+}
+
+//How the rasterizer lights its triangles
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum Shading {
+    //Lit at every vertex and blended across the triangle
+    #[default]
+    Smooth,
+    //Lit once at the center of each triangle and filled with that color, showing the tessellation
+    Flat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub integrator: Integrator,
+    //Rays traced per pixel, rounded to the nearest square so they can be laid out on a grid
+    pub samples_per_pixel: u32,
+    pub sample_pattern: SamplePattern,
+    pub filter: Filter,
+    //Frames rendered and averaged together, each one with a fresh set of random samples
+    pub passes: u32,
+    pub tone_mapping: ToneMapping,
+    //In stops, applied before tone mapping
+    pub exposure: f32,
+    pub shading: Shading,
+    //How the rasterizer's depth buffer compares fragments, and whether the ones that pass store their depth
+    pub depth_test: DepthTest,
+    pub depth_write: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            integrator: Integrator::default(),
+            samples_per_pixel: 1,
+            sample_pattern: SamplePattern::Regular,
+            filter: Filter::default(),
+            passes: 1,
+            tone_mapping: ToneMapping::default(),
+            exposure: 0.0,
+            shading: Shading::default(),
+            depth_test: DepthTest::default(),
+            depth_write: true,
+        }
+    }
+}
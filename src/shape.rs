@@ -0,0 +1,124 @@
+//Shapes are anything a ray can be tested against, the ray tracer only ever sees the hit record they produce
+use crate::bvh::Aabb;
+use crate::properties::Material;
+use cgmath::InnerSpace;
+
+//Everything the renderer needs to know about the closest hit along a ray
+pub struct HitRecord<'shape_lifetime> {
+    pub t: f32,
+    pub point: cgmath::Vector3<f32>,
+    //Unit length, and always facing back against the incoming ray
+    pub normal: cgmath::Vector3<f32>,
+    //Whether the ray hit the outside of the surface, i.e. the normal did not have to be flipped
+    pub front_face: bool,
+    //Surface coordinates of the hit, where the material's textures are looked up
+    pub uv: cgmath::Vector2<f32>,
+    //Unit directions in which u and v increase, perpendicular to the normal, for normal and bump maps
+    pub tangent: cgmath::Vector3<f32>,
+    pub bitangent: cgmath::Vector3<f32>,
+    pub material: &'shape_lifetime Material,
+}
+
+impl<'shape_lifetime> HitRecord<'shape_lifetime> {
+    //Builds a record from the outward facing normal, normalizing and flipping it to face the ray if needed
+    //position_derivatives are how the point moves as u and v increase, their length does not matter
+    pub fn new(
+        t: f32,
+        point: cgmath::Vector3<f32>,
+        outward_normal: cgmath::Vector3<f32>,
+        ray_direction: &cgmath::Vector3<f32>,
+        uv: cgmath::Vector2<f32>,
+        position_derivatives: (cgmath::Vector3<f32>, cgmath::Vector3<f32>),
+        material: &'shape_lifetime Material,
+    ) -> Self {
+        let outward_normal = outward_normal.normalize();
+        let front_face = cgmath::dot(outward_normal, *ray_direction) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+        let (tangent, bitangent) =
+            tangent_frame(&normal, &position_derivatives.0, &position_derivatives.1);
+        Self {
+            t,
+            point,
+            normal,
+            front_face,
+            uv,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+
+    //Replaces the normal used for shading, such as an interpolated or normal mapped one, bending the tangents along with it
+    pub fn set_shading_normal(&mut self, normal: cgmath::Vector3<f32>) {
+        self.normal = normal.normalize();
+        let (tangent, bitangent) = tangent_frame(&self.normal, &self.tangent, &self.bitangent);
+        self.tangent = tangent;
+        self.bitangent = bitangent;
+    }
+}
+
+//Makes the directions of increasing u and v unit length and perpendicular to the normal and each other
+//Falls back to an arbitrary frame where the surface coordinates degenerate, like at the poles of a sphere
+fn tangent_frame(
+    normal: &cgmath::Vector3<f32>,
+    u_direction: &cgmath::Vector3<f32>,
+    v_direction: &cgmath::Vector3<f32>,
+) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    let tangent = u_direction - normal * cgmath::dot(*normal, *u_direction);
+    if tangent.magnitude2() < 1e-12 {
+        return orthonormal_basis(normal);
+    }
+    let tangent = tangent.normalize();
+    //Keep the handedness of the surface coordinates, mirrored uvs flip the bitangent
+    let bitangent = normal.cross(tangent);
+    if cgmath::dot(bitangent, *v_direction) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+pub trait Shape {
+    //min and max distance are measured as the parameter t along the ray origin + t * direction
+    fn intersect(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>>;
+
+    //Whether the ray hits the shape at all, shapes that can answer this faster than finding the closest hit override it
+    fn occluded(
+        &self,
+        origin: &cgmath::Vector3<f32>,
+        direction: &cgmath::Vector3<f32>,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        self.intersect(origin, direction, min_distance, max_distance)
+            .is_some()
+    }
+
+    //Box enclosing the whole shape, None for shapes that are infinite
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+//Two unit vectors perpendicular to the unit length normal and to each other, used to lay out coordinates on a surface
+pub fn orthonormal_basis(
+    normal: &cgmath::Vector3<f32>,
+) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    //Cross with whichever axis is least aligned with the normal to stay numerically stable
+    let helper = if normal.x.abs() > 0.9 {
+        cgmath::Vector3::unit_y()
+    } else {
+        cgmath::Vector3::unit_x()
+    };
+    let tangent = helper.cross(*normal).normalize();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
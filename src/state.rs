@@ -1,20 +1,10 @@
 use crate::buffer_primitives::Vertex;
-use crate::properties::Color;
-use crate::properties::ColorBytes;
+use crate::surface::PixelSurface;
 use crate::texture;
-use std::convert::TryInto;
 use wgpu::util::DeviceExt;
 use winit::event::WindowEvent;
 use winit::window::Window;
 
-static COLOR_BYTE_SIZE: usize = 4; //Color is comprised of 4 bytes, rgba all in u8 form
-
-pub trait Surface {
-    fn set_pixel(&mut self, x: u32, y: u32, color: &Color<u8>);
-    fn get_width(&self) -> u32;
-    fn get_height(&self) -> u32;
-}
-
 pub struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -32,58 +22,8 @@ pub struct State {
     pub texture: texture::Texture,
 }
 
-pub struct PixelSurface {
-    width: u32,
-    height: u32,
-    pixels: Vec<u8>,
-}
-
-impl PixelSurface {
-    fn new(width: u32, height: u32) -> Self {
-        let pixels = vec![
-            0;
-            (width * height * COLOR_BYTE_SIZE as u32) // 4 color channels
-                .try_into()
-                .unwrap()
-        ];
-
-        Self {
-            width,
-            height,
-            pixels,
-        }
-    }
-
-    fn get_pixels(&self) -> &[u8] {
-        &self.pixels
-    }
-}
-
-impl Surface for PixelSurface {
-    fn set_pixel(&mut self, x: u32, y: u32, color: &Color<u8>) {
-        let color_bytes: ColorBytes = color.get_bytes().unwrap();
-        let row_size = self.width * COLOR_BYTE_SIZE as u32;
-        let index: usize = (x * COLOR_BYTE_SIZE as u32 + y * row_size)
-            .try_into()
-            .unwrap();
-        let color_slice = self.pixels.get_mut(index..index + COLOR_BYTE_SIZE);
-        color_slice
-            .unwrap()
-            .copy_from_slice(&bytemuck::bytes_of(&color_bytes)[0..COLOR_BYTE_SIZE]);
-        // turn Color struct into byte array and copy over
-    }
-
-    fn get_width(&self) -> u32 {
-        self.width
-    }
-
-    fn get_height(&self) -> u32 {
-        self.height
-    }
-}
-
 impl State {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window, pixel_width: u32, pixel_height: u32) -> Self {
         let size = window.inner_size();
         //Instance is a handle to our GPU
         //BackendBit is a bitmask defining which backens wgpu will use, PRIMARY enables all of them
@@ -119,11 +59,15 @@ impl State {
         };
         let swap_chain = device.create_swap_chain(&surface, &swap_chain_desc);
 
-        //Need to replace this texture loading with a blank texture, which is exposed to be filled by others
-        let diffuse_bytes = include_bytes!("happy-tree.png");
-        let texture =
-            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, Some("happy-tree.png"))
-                .unwrap();
+        //Blank texture that the pixel surface is copied into every frame
+        let texture = texture::Texture::blank(
+            &device,
+            &queue,
+            pixel_width,
+            pixel_height,
+            Some("pixel_surface"),
+        )
+        .unwrap();
 
         let pixel_surface = PixelSurface::new(texture.size.width, texture.size.height);
 
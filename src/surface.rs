@@ -1,5 +1,84 @@
+//A surface is anything that can be drawn to pixel by pixel, the renderers only ever talk to this trait
+use crate::properties::Color;
+use crate::properties::ColorBytes;
+use anyhow::*;
+use std::convert::TryInto;
+use std::path::Path;
+
+static COLOR_BYTE_SIZE: usize = 4; //Color is comprised of 4 bytes, rgba all in u8 form
+
 pub trait Surface {
     fn set_pixel(&mut self, x: u32, y: u32, color: &Color<u8>);
     fn get_width(&self) -> u32;
     fn get_height(&self) -> u32;
 }
+
+//In memory rgba8 buffer, rows are stored bottom up to match how the window presents them
+pub struct PixelSurface {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl PixelSurface {
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixels = vec![
+            0;
+            (width * height * COLOR_BYTE_SIZE as u32) // 4 color channels
+                .try_into()
+                .unwrap()
+        ];
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    //Writes the surface out as an image, the format is picked from the file extension (png, ppm, ...)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        //Image files are stored top down, so flip the rows on the way out
+        let row_size = self.width as usize * COLOR_BYTE_SIZE;
+        let mut flipped = Vec::with_capacity(self.pixels.len());
+        for row in self.pixels.chunks_exact(row_size).rev() {
+            flipped.extend_from_slice(row);
+        }
+
+        let image = image::RgbaImage::from_raw(self.width, self.height, flipped)
+            .context("Pixel buffer does not match surface dimensions")?;
+        //Alpha is not meaningful in a finished frame, and formats like ppm cannot store it
+        image::DynamicImage::ImageRgba8(image)
+            .to_rgb8()
+            .save(path)
+            .with_context(|| format!("Could not write image {}", path.display()))?;
+        Ok(())
+    }
+}
+
+impl Surface for PixelSurface {
+    fn set_pixel(&mut self, x: u32, y: u32, color: &Color<u8>) {
+        let color_bytes: ColorBytes = color.get_bytes().unwrap();
+        let row_size = self.width * COLOR_BYTE_SIZE as u32;
+        let index: usize = (x * COLOR_BYTE_SIZE as u32 + y * row_size)
+            .try_into()
+            .unwrap();
+        let color_slice = self.pixels.get_mut(index..index + COLOR_BYTE_SIZE);
+        color_slice
+            .unwrap()
+            .copy_from_slice(&bytemuck::bytes_of(&color_bytes)[0..COLOR_BYTE_SIZE]);
+        // turn Color struct into byte array and copy over
+    }
+
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+}
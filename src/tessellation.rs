@@ -0,0 +1,370 @@
+//Turns scene objects into triangles for the rasterizer, in each object's own model space
+//Texture coordinates follow the same layout as the ray tracer's hit records so textured materials line up in both modes
+use crate::lights::Light;
+use crate::objects::Mesh;
+use crate::objects::Object;
+use crate::objects::Plane;
+use crate::properties::Material;
+use crate::scene::Scene;
+use crate::shape::orthonormal_basis;
+use crate::textures::ColorSource;
+use cgmath::InnerSpace;
+use std::f32::consts::PI;
+
+//Rings of latitude and segments of longitude a sphere is split into
+static SPHERE_STACKS: u32 = 24;
+static SPHERE_SLICES: u32 = 48;
+//Planes are infinite, they are drawn as a square this many world units across centered below the camera
+//Triangles are clipped to the view, so a few large ones are enough for the shape
+static PLANE_EXTENT: f32 = 200.0;
+//Size in world units of the tiles of a plane whose lighting changes across it, next to the camera and lights
+//Lighting is only worked out at the corners of the tiles
+static PLANE_TILE_SIZE: f32 = 0.25;
+//How much larger the tiles get for every world unit further away, where they cover less of the screen
+static PLANE_TILE_GROWTH: f32 = 0.125;
+
+#[derive(Debug)]
+pub struct Vertex {
+    pub position: cgmath::Vector3<f32>,
+    pub normal: cgmath::Vector3<f32>,
+    pub uv: cgmath::Vector2<f32>,
+}
+
+#[derive(Debug)]
+pub struct Tessellation {
+    pub vertices: Vec<Vertex>,
+    //Three vertex indices per triangle
+    pub indices: Vec<[u32; 3]>,
+    //Model space to world space, only ever rotates, translates and scales uniformly so it can transform normals too
+    pub model: cgmath::Matrix4<f32>,
+}
+
+pub fn tessellate(object: &Object, scene: &Scene) -> Tessellation {
+    match object {
+        Object::Sphere(sphere) => {
+            let mut tessellation = unit_sphere();
+            tessellation.model = cgmath::Matrix4::from_translation(sphere.center)
+                * cgmath::Matrix4::from_scale(sphere.radius);
+            tessellation
+        }
+        Object::Plane(plane) => plane_grid(plane, scene),
+        Object::Triangle(triangle) => {
+            let normal = (triangle.b - triangle.a).cross(triangle.c - triangle.a);
+            let vertex = |position: cgmath::Vector3<f32>, u: f32, v: f32| Vertex {
+                position,
+                normal,
+                uv: cgmath::Vector2::new(u, v),
+            };
+            Tessellation {
+                vertices: vec![
+                    vertex(triangle.a, 0.0, 0.0),
+                    vertex(triangle.b, 1.0, 0.0),
+                    vertex(triangle.c, 0.0, 1.0),
+                ],
+                indices: vec![[0, 1, 2]],
+                model: cgmath::Matrix4::from_scale(1.0),
+            }
+        }
+        Object::Mesh(mesh) => mesh_triangles(mesh),
+    }
+}
+
+//Sphere of radius 1 around the origin, seams and poles get their own vertices so the texture coordinates don't wrap
+fn unit_sphere() -> Tessellation {
+    let mut vertices = Vec::new();
+    for stack in 0..=SPHERE_STACKS {
+        let v = stack as f32 / SPHERE_STACKS as f32;
+        let latitude = (v - 0.5) * PI;
+        for slice in 0..=SPHERE_SLICES {
+            let u = slice as f32 / SPHERE_SLICES as f32;
+            let longitude = (u - 0.5) * 2.0 * PI;
+            let position = cgmath::Vector3::new(
+                latitude.cos() * longitude.cos(),
+                latitude.sin(),
+                latitude.cos() * longitude.sin(),
+            );
+            vertices.push(Vertex {
+                position,
+                normal: position,
+                uv: cgmath::Vector2::new(u, v),
+            });
+        }
+    }
+    Tessellation {
+        vertices,
+        indices: grid_indices(SPHERE_SLICES, SPHERE_STACKS),
+        model: cgmath::Matrix4::from_scale(1.0),
+    }
+}
+
+//Square grid in the plane's own coordinates, x and y along the same axes as its texture coordinates and z along the normal
+fn plane_grid(plane: &Plane, scene: &Scene) -> Tessellation {
+    let normal = plane.normal.normalize();
+    let (tangent, bitangent) = orthonormal_basis(&normal);
+    let on_plane = |point: cgmath::Vector3<f32>| {
+        cgmath::Vector2::new(
+            cgmath::dot(point - plane.point, tangent),
+            cgmath::dot(point - plane.point, bitangent),
+        )
+    };
+    let center = on_plane(scene.camera.position);
+    let (from, to) = (
+        center - cgmath::Vector2::new(PLANE_EXTENT, PLANE_EXTENT) / 2.0,
+        center + cgmath::Vector2::new(PLANE_EXTENT, PLANE_EXTENT) / 2.0,
+    );
+    let (columns, rows) = if plane_is_uniform(plane, scene) {
+        (vec![from.x, to.x], vec![from.y, to.y])
+    } else {
+        let details: Vec<cgmath::Vector2<f32>> = plane_details(plane, scene)
+            .into_iter()
+            .map(on_plane)
+            .collect();
+        (
+            grid_lines(
+                &details.iter().map(|at| at.x).collect::<Vec<_>>(),
+                from.x,
+                to.x,
+            ),
+            grid_lines(
+                &details.iter().map(|at| at.y).collect::<Vec<_>>(),
+                from.y,
+                to.y,
+            ),
+        )
+    };
+    let mut vertices = Vec::new();
+    for y in rows.iter() {
+        for x in columns.iter() {
+            let uv = cgmath::Vector2::new(*x, *y);
+            vertices.push(Vertex {
+                position: uv.extend(0.0),
+                normal: cgmath::Vector3::unit_z(),
+                uv,
+            });
+        }
+    }
+    Tessellation {
+        vertices,
+        indices: grid_indices(columns.len() as u32 - 1, rows.len() as u32 - 1),
+        model: cgmath::Matrix4::from_cols(
+            tangent.extend(0.0),
+            bitangent.extend(0.0),
+            normal.extend(0.0),
+            plane.point.extend(1.0),
+        ),
+    }
+}
+
+//A flat colored diffuse plane lit only by directional and ambient lights looks the same everywhere and is drawn
+//as a single tile, anything positioned, view dependent or textured needs the finer grid
+fn plane_is_uniform(plane: &Plane, scene: &Scene) -> bool {
+    let uniform_material = matches!(
+        plane.material,
+        Material::Matte {
+            color: ColorSource::Solid(_),
+            ..
+        }
+    );
+    let uniform_lights = scene.emitters().is_empty()
+        && scene
+            .lights
+            .iter()
+            .all(|light| matches!(light, Light::Directional { .. } | Light::Ambient { .. }));
+    uniform_material && uniform_lights
+}
+
+//Points the lighting of the plane changes quickest around, the camera for highlights and where each light is nearest
+//to the plane or, for spot lights, where their cone is centered on it
+fn plane_details(plane: &Plane, scene: &Scene) -> Vec<cgmath::Vector3<f32>> {
+    let mut details = vec![scene.camera.position];
+    for light in scene.lights.iter() {
+        match light {
+            Light::Point { position, .. } => details.push(*position),
+            Light::Spot {
+                position,
+                direction,
+                ..
+            } => {
+                details.push(*position);
+                let towards_plane = cgmath::dot(*direction, plane.normal);
+                if towards_plane != 0.0 {
+                    let t = cgmath::dot(plane.point - position, plane.normal) / towards_plane;
+                    if t > 0.0 {
+                        details.push(position + direction * t);
+                    }
+                }
+            }
+            Light::Area { shape, .. } => details.push(shape.center()),
+            Light::Directional { .. } | Light::Ambient { .. } => {}
+        }
+    }
+    details.extend(
+        scene
+            .emitters()
+            .iter()
+            .map(|emitter| emitter.shape.center()),
+    );
+    details
+}
+
+//Positions of the grid lines from one end of an axis to the other, PLANE_TILE_SIZE apart at the details
+//and further apart the further the nearest detail is
+fn grid_lines(details: &[f32], from: f32, to: f32) -> Vec<f32> {
+    let mut lines = vec![from];
+    let mut at = from;
+    while at < to {
+        let distance = details
+            .iter()
+            .map(|detail| (at - detail).abs())
+            .fold(f32::INFINITY, f32::min);
+        at = (at + PLANE_TILE_SIZE + distance * PLANE_TILE_GROWTH).min(to);
+        lines.push(at);
+    }
+    lines
+}
+
+//Meshes with normals and texture coordinates share their vertices, otherwise every triangle gets its own
+//with the face normal and barycentric coordinates the ray tracer falls back to
+fn mesh_triangles(mesh: &Mesh) -> Tessellation {
+    let model = cgmath::Matrix4::from_scale(1.0);
+    if !mesh.normals.is_empty() && !mesh.uvs.is_empty() {
+        return Tessellation {
+            vertices: (0..mesh.positions.len())
+                .map(|index| Vertex {
+                    position: mesh.positions[index].into(),
+                    normal: mesh.normals[index].into(),
+                    uv: mesh.uvs[index].into(),
+                })
+                .collect(),
+            indices: mesh.indices.clone(),
+            model,
+        };
+    }
+    let mut vertices = Vec::new();
+    for triangle in 0..mesh.triangle_count() {
+        let positions = mesh.triangle_positions(triangle);
+        for (corner, (u, v)) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].iter().enumerate() {
+            vertices.push(Vertex {
+                position: positions[corner],
+                normal: mesh.interpolated_normal(triangle, *u, *v),
+                uv: mesh.interpolated_uv(triangle, *u, *v),
+            });
+        }
+    }
+    let indices = (0..mesh.triangle_count() as u32)
+        .map(|triangle| [triangle * 3, triangle * 3 + 1, triangle * 3 + 2])
+        .collect();
+    Tessellation {
+        vertices,
+        indices,
+        model,
+    }
+}
+
+//Two triangles for every cell of a grid of (columns + 1) by (rows + 1) vertices stored row by row
+fn grid_indices(columns: u32, rows: u32) -> Vec<[u32; 3]> {
+    let mut indices = Vec::new();
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let corner = row * stride + column;
+            indices.push([corner, corner + 1, corner + stride + 1]);
+            indices.push([corner, corner + stride + 1, corner + stride]);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Sphere;
+    use crate::properties::Color;
+
+    #[test]
+    fn sphere_vertices_lie_on_the_sphere_with_ray_traced_uvs() {
+        let sphere = Object::Sphere(Sphere {
+            center: cgmath::Vector3::new(1.0, 2.0, 3.0),
+            radius: 2.0,
+            material: Material::Matte {
+                color: ColorSource::Solid(Color::<u8> {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                    a: 255,
+                }),
+                normal_map: None,
+            },
+        });
+        let scene = Scene::load(r#"{"objects": [], "lights": []}"#).unwrap();
+        let tessellation = tessellate(&sphere, &scene);
+        assert_eq!(
+            tessellation.indices.len() as u32,
+            2 * SPHERE_STACKS * SPHERE_SLICES
+        );
+        for vertex in tessellation.vertices.iter() {
+            let world = (tessellation.model * vertex.position.extend(1.0)).truncate();
+            let offset = world - cgmath::Vector3::new(1.0, 2.0, 3.0);
+            assert!((offset.magnitude() - 2.0).abs() < 1e-4);
+            //Same mapping as Sphere::intersect, away from the seam where u is both 0 and 1
+            let direction = offset / 2.0;
+            let v = 0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI;
+            assert!((vertex.uv.y - v).abs() < 1e-3);
+            if vertex.uv.x > 0.01 && vertex.uv.x < 0.99 && direction.y.abs() < 0.99 {
+                let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+                assert!((vertex.uv.x - u).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_lines_are_closest_together_at_the_details() {
+        let lines = grid_lines(&[0.0, 30.0], -100.0, 100.0);
+        assert_eq!((lines[0], *lines.last().unwrap()), (-100.0, 100.0));
+        let spacing_at = |position: f32| {
+            lines
+                .windows(2)
+                .find(|pair| pair[0] <= position && position < pair[1])
+                .map(|pair| pair[1] - pair[0])
+                .unwrap()
+        };
+        //A tile reaching up to a detail is sized by where it starts, a little before the detail
+        assert!(spacing_at(0.0) < PLANE_TILE_SIZE * 1.2);
+        assert!(spacing_at(30.0) < PLANE_TILE_SIZE * 1.2);
+        assert!(spacing_at(15.0) > spacing_at(5.0));
+        assert!(spacing_at(-90.0) > 5.0);
+        //Far fewer lines than spacing them all PLANE_TILE_SIZE apart
+        assert!(lines.len() < (200.0 / PLANE_TILE_SIZE) as usize / 4);
+    }
+
+    #[test]
+    fn planes_only_get_tiles_where_their_lighting_changes() {
+        let floor = r#"{"Plane": {
+            "point": {"x": 0.0, "y": -1.0, "z": 0.0},
+            "normal": {"x": 0.0, "y": 1.0, "z": 0.0},
+            "material": {"Matte": {"color": [255, 255, 255, 255]}}
+        }}"#;
+        let scene_with = |lights: &str| {
+            Scene::load(&format!(
+                r#"{{"objects": [{}], "lights": [{}]}}"#,
+                floor, lights
+            ))
+            .unwrap()
+        };
+        let directional = r#"{"Directional": {"direction": {"x": 1.0, "y": 4.0, "z": 4.0}, "intensity": [0.8, 0.8, 0.8, 1.0]}}"#;
+        let scene = scene_with(directional);
+        assert_eq!(tessellate(&scene.objects[0], &scene).indices.len(), 2);
+        let point = r#"{"Point": {"position": {"x": 20.0, "y": 1.0, "z": 10.0}, "intensity": [0.6, 0.6, 0.6, 1.0]}}"#;
+        let scene = scene_with(&format!("{}, {}", directional, point));
+        let tessellation = tessellate(&scene.objects[0], &scene);
+        //Vertices right below the light, a fixed number of world units apart regardless of the plane's extent
+        let below_light = cgmath::Vector3::new(20.0, -1.0, 10.0);
+        let near_light = tessellation
+            .vertices
+            .iter()
+            .map(|vertex| (tessellation.model * vertex.position.extend(1.0)).truncate())
+            .filter(|position| (position - below_light).magnitude() < PLANE_TILE_SIZE * 1.5)
+            .count();
+        assert!(near_light >= 4);
+    }
+}
@@ -10,13 +10,15 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn from_bytes(
+    //Creates a zeroed rgba texture of the given size, to be filled later with fill_texture
+    pub fn blank(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        bytes: &[u8],
+        width: u32,
+        height: u32,
         label: Option<&str>,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = image::DynamicImage::new_rgba8(width, height);
         Self::from_image(device, queue, &img, label)
     }
 
@@ -0,0 +1,571 @@
+//Textures materials can take their colors from, looked up by the surface coordinates or position of a hit
+//Not to be confused with texture.rs, which uploads finished frames to the gpu
+use crate::noise::perlin;
+use crate::noise::turbulence;
+use crate::properties::Color;
+use crate::shape::HitRecord;
+use anyhow::*;
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+//Anywhere a material takes a color it can either be a flat color, as before, or a texture
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ColorSource {
+    Solid(Color<u8>),
+    Texture(TextureMap),
+    //A texture already looked up at a hit by Material::at, kept linear so it isn't rounded to 8 bits
+    #[serde(skip_deserializing)]
+    Linear(Color<f32>),
+}
+
+impl ColorSource {
+    //Linear color at the surface coordinates, or at the point in space for solid textures
+    pub fn sample(&self, uv: &cgmath::Vector2<f32>, point: &cgmath::Vector3<f32>) -> Color<f32> {
+        match self {
+            ColorSource::Solid(color) => color.to_linear(),
+            ColorSource::Texture(texture) => texture.sample(uv, point, Encoding::Srgb),
+            ColorSource::Linear(color) => *color,
+        }
+    }
+
+    //Single linear color standing in for the whole surface, for places that can't look up texture coordinates
+    pub fn get_color(&self) -> Color<f32> {
+        self.sample(
+            &cgmath::Vector2::new(0.5, 0.5),
+            &cgmath::Vector3::new(0.0, 0.0, 0.0),
+        )
+    }
+}
+
+//How the values stored in a texture are read, colors are sRGB encoded while data such as normals, heights,
+//roughness and metallic are used as stored
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Encoding {
+    Srgb,
+    Linear,
+}
+
+impl Encoding {
+    fn decode(self, stored: Color<u8>) -> Color<f32> {
+        match self {
+            Encoding::Srgb => stored.to_linear(),
+            Encoding::Linear => stored.to_unit(),
+        }
+    }
+}
+
+//Checker, stripes and gradients are laid out in surface coordinates
+//Noise, turbulence and marble are solid textures evaluated at the hit point, so they don't stretch or seam
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TextureMap {
+    Image(ImageTexture),
+    //Squares alternating between even and odd, scale of them per unit of u and v
+    Checker {
+        even: Color<u8>,
+        odd: Color<u8>,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    //Bands alternating between even and odd across u, turned by angle in degrees
+    Stripes {
+        even: Color<u8>,
+        odd: Color<u8>,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default)]
+        angle: f32,
+    },
+    //Blends from start to end as u, or v, goes from 0 to 1
+    Gradient {
+        start: Color<u8>,
+        end: Color<u8>,
+        #[serde(default)]
+        axis: GradientAxis,
+    },
+    //Perlin noise blending between low and high, scale is the number of noise cells per world unit
+    Noise {
+        low: Color<u8>,
+        high: Color<u8>,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    Turbulence {
+        low: Color<u8>,
+        high: Color<u8>,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+    },
+    //Sine bands along x distorted by turbulence, strength sets how far the veins wander
+    Marble {
+        low: Color<u8>,
+        high: Color<u8>,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_marble_strength")]
+        strength: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum GradientAxis {
+    #[default]
+    U,
+    V,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    6
+}
+
+fn default_marble_strength() -> f32 {
+    5.0
+}
+
+impl TextureMap {
+    //Values are decoded before they are filtered or blended, so both happen in the space they are used in
+    pub fn sample(
+        &self,
+        uv: &cgmath::Vector2<f32>,
+        point: &cgmath::Vector3<f32>,
+        encoding: Encoding,
+    ) -> Color<f32> {
+        match self {
+            TextureMap::Image(image) => image.sample(uv, encoding),
+            TextureMap::Checker { even, odd, scale } => {
+                let cell = (uv.x * scale).floor() + (uv.y * scale).floor();
+                if cell.rem_euclid(2.0) < 1.0 {
+                    encoding.decode(*even)
+                } else {
+                    encoding.decode(*odd)
+                }
+            }
+            TextureMap::Stripes {
+                even,
+                odd,
+                scale,
+                angle,
+            } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let across = (uv.x * cos + uv.y * sin) * scale;
+                if across.floor().rem_euclid(2.0) < 1.0 {
+                    encoding.decode(*even)
+                } else {
+                    encoding.decode(*odd)
+                }
+            }
+            TextureMap::Gradient { start, end, axis } => {
+                let t = match axis {
+                    GradientAxis::U => uv.x,
+                    GradientAxis::V => uv.y,
+                };
+                blend(start, end, t, encoding)
+            }
+            TextureMap::Noise { low, high, scale } => {
+                blend(low, high, 0.5 + 0.5 * perlin(&(point * *scale)), encoding)
+            }
+            TextureMap::Turbulence {
+                low,
+                high,
+                scale,
+                octaves,
+            } => blend(low, high, turbulence(&(point * *scale), *octaves), encoding),
+            TextureMap::Marble {
+                low,
+                high,
+                scale,
+                octaves,
+                strength,
+            } => {
+                let scaled = point * *scale;
+                let phase = scaled.x + strength * turbulence(&scaled, *octaves);
+                blend(low, high, 0.5 + 0.5 * phase.sin(), encoding)
+            }
+        }
+    }
+
+    //Procedural textures have nothing to load
+    pub fn load_images(&mut self, base_directory: &Path, cache: &mut ImageCache) -> Result<()> {
+        match self {
+            TextureMap::Image(image) => image.load(base_directory, cache),
+            _ => Ok(()),
+        }
+    }
+}
+
+//Mixes the decoded values, which for colors is in linear light, t is clamped to 0 to 1
+fn blend(from: &Color<u8>, to: &Color<u8>, t: f32, encoding: Encoding) -> Color<f32> {
+    let t = t.clamp(0.0, 1.0);
+    encoding.decode(*from) * (1.0 - t) + encoding.decode(*to) * t
+}
+
+//Adds surface detail by bending the shading normal, the geometry and the normal used for intersections stay the same
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum NormalMap {
+    //Tangent space normals encoded as colors the way most tools export them, red along u and green along v
+    Tangent {
+        texture: TextureMap,
+        //Scales the tilt away from the surface normal, 0 leaves it flat
+        #[serde(default = "default_strength")]
+        strength: f32,
+    },
+    //Greyscale height field, the normal tilts away from the direction the height rises in
+    Bump {
+        height: TextureMap,
+        //Height change per unit of u and v that corresponds to a 45 degree slope
+        #[serde(default = "default_strength")]
+        strength: f32,
+    },
+}
+
+fn default_strength() -> f32 {
+    1.0
+}
+
+//Step in surface coordinates for the finite differences of bump maps, solid textures step the same distance along the tangents
+static BUMP_STEP: f32 = 0.001;
+
+impl NormalMap {
+    pub fn texture_mut(&mut self) -> &mut TextureMap {
+        match self {
+            NormalMap::Tangent { texture, .. } => texture,
+            NormalMap::Bump { height, .. } => height,
+        }
+    }
+
+    //Shading normal at the hit, perturbed within the hit's tangent frame
+    pub fn perturb(&self, hit: &HitRecord) -> cgmath::Vector3<f32> {
+        let perturbed = match self {
+            NormalMap::Tangent { texture, strength } => {
+                let texel = texture.sample(&hit.uv, &hit.point, Encoding::Linear);
+                let component = |stored: f32| stored * 2.0 - 1.0;
+                hit.tangent * (component(texel.r) * strength)
+                    + hit.bitangent * (component(texel.g) * strength)
+                    + hit.normal * component(texel.b)
+            }
+            NormalMap::Bump { height, strength } => {
+                let height_at = |du: f32, dv: f32| {
+                    let uv = hit.uv + cgmath::Vector2::new(du, dv);
+                    let point = hit.point + hit.tangent * du + hit.bitangent * dv;
+                    let texel = height.sample(&uv, &point, Encoding::Linear);
+                    (texel.r + texel.g + texel.b) / 3.0
+                };
+                let center = height_at(0.0, 0.0);
+                let slope_u = (height_at(BUMP_STEP, 0.0) - center) / BUMP_STEP;
+                let slope_v = (height_at(0.0, BUMP_STEP) - center) / BUMP_STEP;
+                hit.normal - (hit.tangent * slope_u + hit.bitangent * slope_v) * *strength
+            }
+        };
+        //A normal tilted past the surface would light it from behind, keep the geometric one instead
+        if cgmath::dot(perturbed, hit.normal) <= 0.0 {
+            return hit.normal;
+        }
+        perturbed.normalize()
+    }
+}
+
+//What happens to texture coordinates outside 0 to 1
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+//Pixels of an image file as stored, they are only decoded when sampled as the same file may be used for colors or data
+#[derive(Debug)]
+pub struct Texels {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color<u8>>,
+}
+
+//Images already decoded while loading a scene, so materials sharing a file share its pixels
+pub type ImageCache = HashMap<PathBuf, Arc<Texels>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageTexture {
+    //Relative paths are resolved against the directory of the scene file
+    pub path: PathBuf,
+    #[serde(default)]
+    pub wrap: WrapMode,
+    #[serde(default)]
+    pub filter: TextureFilter,
+    //Filled in by load, until then the texture samples as magenta so the missing image stands out
+    #[serde(skip)]
+    texels: Option<Arc<Texels>>,
+}
+
+impl ImageTexture {
+    pub fn load(&mut self, base_directory: &Path, cache: &mut ImageCache) -> Result<()> {
+        let path = base_directory.join(&self.path);
+        if let Some(texels) = cache.get(&path) {
+            self.texels = Some(texels.clone());
+            return Ok(());
+        }
+        let image = image::open(&path)
+            .with_context(|| format!("Could not load texture {}", path.display()))?
+            .to_rgba8();
+        let texels = Arc::new(Texels {
+            width: image.width(),
+            height: image.height(),
+            pixels: image
+                .pixels()
+                .map(|pixel| Color::<u8> {
+                    r: pixel[0],
+                    g: pixel[1],
+                    b: pixel[2],
+                    a: pixel[3],
+                })
+                .collect(),
+        });
+        cache.insert(path, texels.clone());
+        self.texels = Some(texels);
+        Ok(())
+    }
+
+    //v runs from the bottom of the image to the top
+    pub fn sample(&self, uv: &cgmath::Vector2<f32>, encoding: Encoding) -> Color<f32> {
+        let texels = match &self.texels {
+            Some(texels) => texels,
+            None => {
+                return Color::<f32> {
+                    r: 1.0,
+                    g: 0.0,
+                    b: 1.0,
+                    a: 1.0,
+                }
+            }
+        };
+        //Texel centers sit at half integer positions
+        let x = uv.x * texels.width as f32 - 0.5;
+        let y = (1.0 - uv.y) * texels.height as f32 - 0.5;
+        match self.filter {
+            TextureFilter::Nearest => {
+                encoding.decode(self.texel(texels, x.round() as i64, y.round() as i64))
+            }
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fraction_x, fraction_y) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let texel = |x: i64, y: i64| encoding.decode(self.texel(texels, x, y));
+                let top = texel(x0, y0) * (1.0 - fraction_x) + texel(x0 + 1, y0) * fraction_x;
+                let bottom =
+                    texel(x0, y0 + 1) * (1.0 - fraction_x) + texel(x0 + 1, y0 + 1) * fraction_x;
+                top * (1.0 - fraction_y) + bottom * fraction_y
+            }
+        }
+    }
+
+    fn texel(&self, texels: &Texels, x: i64, y: i64) -> Color<u8> {
+        let x = wrap(x, texels.width as i64, self.wrap);
+        let y = wrap(y, texels.height as i64, self.wrap);
+        texels.pixels[(x + y * texels.width as i64) as usize]
+    }
+}
+
+//Brings a texel index into 0..size according to the wrap mode
+fn wrap(index: i64, size: i64, mode: WrapMode) -> i64 {
+    match mode {
+        WrapMode::Repeat => index.rem_euclid(size),
+        WrapMode::MirroredRepeat => {
+            let index = index.rem_euclid(2 * size);
+            if index < size {
+                index
+            } else {
+                2 * size - 1 - index
+            }
+        }
+        WrapMode::ClampToEdge => index.clamp(0, size - 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::properties::Material;
+
+    //2x2, dark and light on the top row and light and dark on the bottom
+    fn checker_texture(wrap: WrapMode, filter: TextureFilter, dark: u8, light: u8) -> ImageTexture {
+        let grey = |value: u8| Color::<u8> {
+            r: value,
+            g: value,
+            b: value,
+            a: u8::MAX,
+        };
+        ImageTexture {
+            path: PathBuf::new(),
+            wrap,
+            filter,
+            texels: Some(Arc::new(Texels {
+                width: 2,
+                height: 2,
+                pixels: vec![grey(dark), grey(light), grey(light), grey(dark)],
+            })),
+        }
+    }
+
+    #[test]
+    fn wrap_modes_fold_indices_back_into_range() {
+        assert_eq!(wrap(-1, 4, WrapMode::Repeat), 3);
+        assert_eq!(wrap(5, 4, WrapMode::Repeat), 1);
+        assert_eq!(wrap(-1, 4, WrapMode::MirroredRepeat), 0);
+        assert_eq!(wrap(5, 4, WrapMode::MirroredRepeat), 2);
+        assert_eq!(wrap(-3, 4, WrapMode::ClampToEdge), 0);
+        assert_eq!(wrap(9, 4, WrapMode::ClampToEdge), 3);
+    }
+
+    #[test]
+    fn bilinear_filter_blends_neighbouring_texels() {
+        let nearest = checker_texture(WrapMode::Repeat, TextureFilter::Nearest, 0, 255);
+        let bilinear = checker_texture(WrapMode::Repeat, TextureFilter::Bilinear, 0, 255);
+        //Center of the top left texel
+        let top_left = cgmath::Vector2::new(0.25, 0.75);
+        assert_eq!(nearest.sample(&top_left, Encoding::Srgb).r, 0.0);
+        assert!(bilinear.sample(&top_left, Encoding::Srgb).r.abs() < 1e-6);
+        //Halfway between all four texels
+        let middle = cgmath::Vector2::new(0.5, 0.5);
+        assert!((bilinear.sample(&middle, Encoding::Srgb).r - 0.5).abs() < 1e-6);
+        //Repeating makes the seam blend the last column with the first
+        let seam = cgmath::Vector2::new(1.0, 0.75);
+        assert!((bilinear.sample(&seam, Encoding::Srgb).r - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn data_is_filtered_as_stored() {
+        let texture = checker_texture(WrapMode::Repeat, TextureFilter::Bilinear, 0, 128);
+        let middle = cgmath::Vector2::new(0.5, 0.5);
+        assert!((texture.sample(&middle, Encoding::Linear).r - 64.0 / 255.0).abs() < 1e-6);
+        //Colors are decoded first and then averaged in linear light
+        let expected = Color::<u8> {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: u8::MAX,
+        }
+        .to_linear()
+        .r / 2.0;
+        assert!((texture.sample(&middle, Encoding::Srgb).r - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn flat_colors_and_textures_both_deserialize() {
+        let solid: ColorSource = serde_json::from_str("[10, 20, 30, 255]").unwrap();
+        assert!(matches!(solid, ColorSource::Solid(_)));
+        let image: ColorSource =
+            serde_json::from_str(r#"{"Image": {"path": "bricks.png", "wrap": "ClampToEdge"}}"#)
+                .unwrap();
+        match image {
+            ColorSource::Texture(TextureMap::Image(image)) => {
+                assert_eq!(image.wrap, WrapMode::ClampToEdge);
+                assert_eq!(image.filter, TextureFilter::Bilinear);
+            }
+            _ => panic!("expected an image texture"),
+        }
+    }
+
+    #[test]
+    fn checker_alternates_across_cells() {
+        let checker: TextureMap = serde_json::from_str(
+            r#"{"Checker": {"even": [0, 0, 0, 255], "odd": [255, 255, 255, 255], "scale": 2.0}}"#,
+        )
+        .unwrap();
+        let origin = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        let at = |u: f32, v: f32| {
+            checker
+                .sample(&cgmath::Vector2::new(u, v), &origin, Encoding::Srgb)
+                .r
+        };
+        assert_eq!(at(0.1, 0.1), 0.0);
+        assert_eq!(at(0.6, 0.1), 1.0);
+        assert_eq!(at(0.6, 0.6), 0.0);
+        //Negative coordinates keep alternating instead of mirroring around 0
+        assert_eq!(at(-0.1, 0.1), 1.0);
+        assert_eq!(at(-0.6, 0.1), 0.0);
+    }
+
+    #[test]
+    fn bump_map_tilts_the_normal_away_from_rising_height() {
+        let material = Material::Matte {
+            color: ColorSource::Solid(Color::<u8> {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            }),
+            normal_map: None,
+        };
+        //Height rises along u
+        let ramp = TextureMap::Gradient {
+            start: Color::<u8> {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255,
+            },
+            end: Color::<u8> {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            },
+            axis: GradientAxis::U,
+        };
+        let hit = HitRecord::new(
+            1.0,
+            cgmath::Vector3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::new(0.0, 0.0, 1.0),
+            &cgmath::Vector3::new(0.0, 0.0, -1.0),
+            cgmath::Vector2::new(0.5, 0.5),
+            (cgmath::Vector3::unit_x(), cgmath::Vector3::unit_y()),
+            &material,
+        );
+        let bump = NormalMap::Bump {
+            height: ramp,
+            strength: 1.0,
+        };
+        let normal = bump.perturb(&hit);
+        assert!(normal.x < -0.1);
+        assert!(normal.y.abs() < 1e-3);
+        assert!((normal.magnitude() - 1.0).abs() < 1e-5);
+        //A flat tangent space normal leaves the surface as it was
+        let flat = NormalMap::Tangent {
+            texture: TextureMap::Checker {
+                even: Color::<u8> {
+                    r: 128,
+                    g: 128,
+                    b: 255,
+                    a: 255,
+                },
+                odd: Color::<u8> {
+                    r: 128,
+                    g: 128,
+                    b: 255,
+                    a: 255,
+                },
+                scale: 1.0,
+            },
+            strength: 1.0,
+        };
+        assert!((flat.perturb(&hit) - hit.normal).magnitude() < 0.01);
+    }
+}
@@ -0,0 +1,108 @@
+//Brings the unbounded linear colors of the film into the 0 to 1 range before they are encoded as sRGB
+use crate::properties::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub enum ToneMapping {
+    //Anything brighter than 1 is cut off, matches the output from before tone mapping existed
+    #[default]
+    Clamp,
+    //L / (1 + L) on the luminance, never reaches white
+    Reinhard,
+    //Reinhard with luminance white_point (and above) mapped to white
+    ExtendedReinhard {
+        white_point: f32,
+    },
+    //Narkowicz's fit of the ACES filmic curve, applied per channel
+    Aces,
+}
+
+impl ToneMapping {
+    //exposure is in stops, every stop doubles the brightness before the curve is applied
+    pub fn apply(&self, color: Color<f32>, exposure: f32) -> Color<f32> {
+        let exposed = color * 2f32.powf(exposure);
+        let mapped = match *self {
+            ToneMapping::Clamp => exposed,
+            ToneMapping::Reinhard => {
+                scale_luminance(exposed, |luminance| luminance / (1.0 + luminance))
+            }
+            ToneMapping::ExtendedReinhard { white_point } => {
+                scale_luminance(exposed, |luminance| {
+                    luminance * (1.0 + luminance / (white_point * white_point)) / (1.0 + luminance)
+                })
+            }
+            ToneMapping::Aces => Color::<f32> {
+                r: aces(exposed.r),
+                g: aces(exposed.g),
+                b: aces(exposed.b),
+                a: exposed.a,
+            },
+        };
+        Color::<f32> {
+            r: mapped.r.clamp(0.0, 1.0),
+            g: mapped.g.clamp(0.0, 1.0),
+            b: mapped.b.clamp(0.0, 1.0),
+            a: color.a,
+        }
+    }
+}
+
+//Rec. 709 luminance of a linear color
+fn luminance(color: &Color<f32>) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+//Maps the luminance through the curve and scales the channels by the same amount, which keeps the hue
+fn scale_luminance(color: Color<f32>, curve: impl Fn(f32) -> f32) -> Color<f32> {
+    let luminance = luminance(&color);
+    if luminance <= 0.0 {
+        return color;
+    }
+    color * (curve(luminance) / luminance)
+}
+
+fn aces(value: f32) -> f32 {
+    let value = value.max(0.0);
+    (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: f32) -> Color<f32> {
+        Color::<f32> {
+            r: value,
+            g: value,
+            b: value,
+            a: 1.0,
+        }
+    }
+
+    #[test]
+    fn curves_stay_in_range_and_keep_order() {
+        let operators = [
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::ExtendedReinhard { white_point: 4.0 },
+            ToneMapping::Aces,
+        ];
+        for operator in operators.iter() {
+            let mut previous = 0.0;
+            for step in 0..100 {
+                let mapped = operator.apply(grey(step as f32 * 0.1), 0.0).r;
+                assert!((0.0..=1.0).contains(&mapped));
+                assert!(mapped >= previous);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_white() {
+        let operator = ToneMapping::ExtendedReinhard { white_point: 4.0 };
+        assert!((operator.apply(grey(4.0), 0.0).r - 1.0).abs() < 1e-5);
+        //One stop of exposure brings 2 up to the white point
+        assert!((operator.apply(grey(2.0), 1.0).r - 1.0).abs() < 1e-5);
+    }
+}